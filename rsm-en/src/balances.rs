@@ -3,7 +3,11 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pallet {
-    pub balances: BTreeMap<String, u128> // String for key, u128 for unsigned for positive-only vals
+    pub balances: BTreeMap<String, u128>, // String for key, u128 for unsigned for positive-only vals
+    #[serde(default)]
+    total_issuance: u128, // Sum of every balance, kept in step by set_balance, mint and burn
+    #[serde(default)]
+    pub existential_deposit: u128 // Smallest non-zero balance a transfer may leave a sender with; 0 disables the check
 }
 
 impl Pallet {
@@ -12,16 +16,73 @@ impl Pallet {
         
         Self {
 
-            balances: BTreeMap::new()
+            balances: BTreeMap::new(),
+            total_issuance: 0,
+            existential_deposit: 0
         }
     }
 
-    pub fn set_balance(&mut self, who: &String, amount: u128){
+    /// Fails, changing nothing, if total issuance can't account for the old
+    /// balance or can't hold the new one
+    pub fn set_balance(&mut self, who: &str, amount: u128) -> Result<(), &'static str>{
 
-        self.balances.insert(who.clone(), amount);
+        let previous: u128 = self.get_balance(&who.to_string());
+        let total_issuance: u128 = self
+            .total_issuance
+            .checked_sub(previous)
+            .ok_or("Total issuance is less than the balances it covers")?
+            .checked_add(amount)
+            .ok_or("Total issuance overflow")?;
+
+        // Accounts that drop to zero are removed rather than left lingering
+        if amount == 0 {
+            self.balances.remove(who);
+        } else {
+            self.balances.insert(who.to_string(), amount);
+        }
+        self.total_issuance = total_issuance;
+
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn set_existential_deposit(&mut self, deposit: u128){
+
+        self.existential_deposit = deposit;
+    }
+
+    /// Removes `who` entirely, burning whatever dust it still held. Returns
+    /// the amount burned.
+    #[allow(dead_code)]
+    pub fn reap_account(&mut self, who: &str) -> u128 {
+
+        let balance: u128 = self.balances.remove(who).unwrap_or(0);
+        self.total_issuance -= balance;
+        balance
+    }
+
+    pub fn total_issuance(&self) -> u128 {
+
+        self.total_issuance
+    }
+
+    pub fn mint(&mut self, who: &str, amount: u128) -> Result<(), &'static str>{
+
+        let balance: u128 = self.get_balance(&who.to_string());
+        let new_balance: u128 = balance.checked_add(amount).ok_or("Error adding balance")?;
+
+        self.set_balance(who, new_balance)
+    }
+
+    pub fn burn(&mut self, who: &str, amount: u128) -> Result<(), &'static str>{
+
+        let balance: u128 = self.get_balance(&who.to_string());
+        let new_balance: u128 = balance.checked_sub(amount).ok_or("Insufficient balance to burn")?;
+
+        self.set_balance(who, new_balance)
     }
 
-    pub fn get_balance(&mut self, who: &String) -> u128{
+    pub fn get_balance(&self, who: &String) -> u128{
         
         *self.balances.get(who).unwrap_or(&0)
     }
@@ -32,11 +93,65 @@ impl Pallet {
         let reciever_balance: u128 = self.get_balance(&reciever);
 
         let new_sender_balance: u128 = sender_balance.checked_sub(amount).ok_or("Insufficient sender balance")?;
+        if new_sender_balance > 0 && new_sender_balance < self.existential_deposit {
+            return Err("Transfer would leave sender below the existential deposit");
+        }
         let new_reciever_balance: u128 = reciever_balance.checked_add(amount).ok_or("Error adding balance")?;
 
-        self.set_balance(&sender, new_sender_balance);
-        self.set_balance(&reciever, new_reciever_balance);
+        self.set_balance(&sender, new_sender_balance)?;
+        self.set_balance(&reciever, new_reciever_balance)
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_transfer_removes_sender() {
+        let mut balances = Pallet::new();
+        balances.set_balance("alice", 100).unwrap();
+
+        balances.transfer("alice".to_string(), "bob".to_string(), 100).unwrap();
+        assert!(!balances.balances.contains_key("alice"));
+        assert_eq!(balances.get_balance(&"alice".to_string()), 0);
+        assert_eq!(balances.get_balance(&"bob".to_string()), 100);
+        assert_eq!(balances.total_issuance(), 100);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_existential_deposit() {
+        let mut balances = Pallet::new();
+        balances.set_existential_deposit(10);
+        balances.set_balance("alice", 100).unwrap();
+
+        // Leaving dust behind is refused, emptying the account is not
+        assert!(balances.transfer("alice".to_string(), "bob".to_string(), 95).is_err());
+        assert_eq!(balances.get_balance(&"alice".to_string()), 100);
+        balances.transfer("alice".to_string(), "bob".to_string(), 90).unwrap();
+        balances.transfer("alice".to_string(), "bob".to_string(), 10).unwrap();
+        assert!(!balances.balances.contains_key("alice"));
+
+        // Reaping burns what's left
+        assert_eq!(balances.reap_account("bob"), 100);
+        assert!(balances.balances.is_empty());
+        assert_eq!(balances.total_issuance(), 0);
+    }
+
+    #[test]
+    fn test_set_balance_checks_total_issuance() {
+        // As deserialized from a file that left the issuance out
+        let mut balances: Pallet = serde_json::from_str(r#"{"balances":{"alice":100}}"#).unwrap();
+        assert_eq!(balances.total_issuance(), 0);
+
+        assert!(balances.transfer("alice".to_string(), "bob".to_string(), 10).is_err());
+        assert!(balances.set_balance("alice", 50).is_err());
+        assert_eq!(balances.get_balance(&"alice".to_string()), 100);
+        assert_eq!(balances.total_issuance(), 0);
+
+        let mut balances = Pallet::new();
+        balances.set_balance("alice", u128::MAX).unwrap();
+        assert!(balances.mint("bob", 1).is_err());
+        assert_eq!(balances.get_balance(&"bob".to_string()), 0);
+    }
+}
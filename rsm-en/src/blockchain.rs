@@ -1,35 +1,226 @@
-use crate::merkle::{Hash, FastMerkleTree};
-use crate::transaction::{Transaction, Block};
-use crate::system::Pallet as SystemPallet;
+use crate::merkle::{verify_proof_against_root, Hash, FastMerkleTree};
+use crate::transaction::{current_timestamp, Transaction, Block, BlockHeader};
+use crate::system::{Event, Pallet as SystemPallet};
 use crate::balances::Pallet as BalancesPallet;
-use crate::rps_mining::RPSMiner;
+use crate::rps_mining::{MiningError, ProgressCallback, RPSMiner, RPSMiningConfig, RPSMiningResult};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Sender of the mining reward transaction in every block
+pub const NETWORK_ACCOUNT: &str = "network";
+
+/// Holds the initial supply created with the genesis block
+pub const GENESIS_ACCOUNT: &str = "genesis";
+
+/// Most PhlopCoin a single block can earn. A block can't be mined in fewer
+/// games than its difficulty requires, so an honest n / a^2 never exceeds 1.
+pub const MAX_PHLOPCOIN_REWARD: f64 = 1.0;
+
+/// Paid instead when the reward formula doesn't produce a finite number
+pub const MIN_PHLOPCOIN_REWARD: f64 = 0.0;
+
+/// Which of `Blockchain::audit`'s checks a block failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditCheck {
+    Linkage,
+    BlockHash,
+    MerkleRoot,
+    Transaction,
+    RpsProof,
+    Coinbase,
+    StateRoot,
+}
+
+/// One problem `Blockchain::audit` found with a block
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditFinding {
+    pub block_index: u32,
+    pub check: AuditCheck,
+    pub reason: String,
+}
+
+/// Why a transaction was refused or a block couldn't be mined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockchainError {
+    /// Its hash doesn't match its contents, a party is missing or it pays itself
+    InvalidTransaction,
+    /// Past its `valid_until`
+    TransactionExpired,
+    /// Sent from one of the chain's `reserved_senders`
+    ReservedSender(String),
+    InsufficientBalance { have: u128, need: u128 },
+    InvalidNonce { expected: u32, got: u32 },
+    /// No room left to hold transactions waiting on a nonce gap
+    QueueFull,
+    /// The mempool is full and nothing in it pays a lower fee
+    MempoolFull,
+    /// An amount plus fee, or the block reward plus fees, exceeds a u128
+    Overflow(&'static str),
+    /// The balances pallet refused to apply a change
+    State(&'static str),
+    /// RPS mining gave up on the block
+    Mining(MiningError),
+    /// An imported chain isn't JSON for a `Blockchain`
+    InvalidJson(String),
+    /// An imported chain's balances aren't what its transactions add up to
+    BalanceMismatch,
+    /// An imported chain's total issuance isn't the sum of its balances
+    SupplyMismatch(String),
+    /// An imported chain failed `audit`, first at `block_index`
+    InvalidChain { block_index: u32, reason: String },
+}
+
+impl fmt::Display for BlockchainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockchainError::InvalidTransaction => write!(f, "Invalid transaction"),
+            BlockchainError::TransactionExpired => write!(f, "Transaction has expired"),
+            BlockchainError::ReservedSender(sender) => {
+                write!(f, "'{}' is a reserved account and cannot send transactions", sender)
+            }
+            BlockchainError::InsufficientBalance { .. } => write!(f, "Insufficient balance"),
+            BlockchainError::InvalidNonce { .. } => write!(f, "Invalid nonce"),
+            BlockchainError::QueueFull => write!(f, "Transaction queue is full"),
+            BlockchainError::MempoolFull => write!(f, "Mempool is full and the fee is too low to replace anything"),
+            BlockchainError::Overflow(what) => write!(f, "{} overflows", what),
+            BlockchainError::State(reason) => write!(f, "{}", reason),
+            BlockchainError::Mining(e) => write!(f, "RPS Mining failed: {}", e),
+            BlockchainError::InvalidJson(e) => write!(f, "Failed to parse blockchain: {}", e),
+            BlockchainError::BalanceMismatch => write!(f, "Balances don't match the transaction history"),
+            BlockchainError::SupplyMismatch(reason) => write!(f, "{}", reason),
+            BlockchainError::InvalidChain { block_index, reason } => {
+                write!(f, "Block {} is invalid: {}", block_index, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockchainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BlockchainError::Mining(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<MiningError> for BlockchainError {
+    fn from(e: MiningError) -> Self {
+        BlockchainError::Mining(e)
+    }
+}
+
+/// The balances pallet reports its errors as plain messages
+impl From<&'static str> for BlockchainError {
+    fn from(reason: &'static str) -> Self {
+        BlockchainError::State(reason)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
     pub difficulty: usize,
     pub pending_transactions: VecDeque<Transaction>,
+    /// Transactions whose nonce is ahead of the sender's next one, keyed by
+    /// sender then nonce. They move into the mempool once the gap is filled.
+    #[serde(default)]
+    pub queued_transactions: BTreeMap<String, BTreeMap<u32, Transaction>>,
+    /// Base reward for the next block; change it with `set_mining_reward`
     pub mining_reward: u128,
+    /// The base reward each change took effect at, by block index, so past
+    /// blocks are still checked against the reward they were mined under
+    #[serde(default)]
+    pub reward_schedule: BTreeMap<u32, u128>,
     pub system: SystemPallet,
     pub balances: BalancesPallet,
     pub rps_miner: RPSMiner,
+    /// Balances the genesis block funds, which every replay starts from
+    #[serde(default = "default_genesis_allocations")]
+    pub genesis_allocations: BTreeMap<String, u128>,
+    /// Accounts only the chain itself may send from; `add_transaction`
+    /// rejects user transactions from any of them
+    pub reserved_senders: BTreeSet<String>,
+    /// Most transactions a mined block holds, its coinbase included
+    pub max_transactions_per_block: usize,
+    /// Blocks between each halving of `mining_reward`
+    pub halving_interval: u64,
+    /// Recent blocks `get_network_hash_rate` averages over
+    pub hash_rate_window: usize,
+    /// Most transactions the mempool holds before evicting by fee
+    pub max_mempool_size: usize,
+    /// Account state as of the last block `prune` emptied, which replays
+    /// start from in place of the genesis allocations
+    #[serde(default)]
+    pub pruned_state: Option<PrunedState>,
+    /// Block index and position of every transaction in the chain, by hash.
+    /// It's derived from `chain`, so rather than being saved it's rebuilt
+    /// whenever a chain is loaded.
+    #[serde(skip)]
+    tx_index: HashMap<Hash, (u32, usize)>,
+    /// The difficulty score each block after genesis was mined at, by block
+    /// index. Derived from `chain` like `tx_index`, and rebuilt with it.
+    #[serde(skip)]
+    difficulty_history: Vec<(u32, f64)>,
+}
+
+/// The state left by every block up to `through`, kept once their
+/// transactions have been pruned away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunedState {
+    pub through: u32,
+    pub balances: BalancesPallet,
+    pub system: SystemPallet,
+}
+
+/// The demo accounts `Blockchain::new` funds
+fn default_genesis_allocations() -> BTreeMap<String, u128> {
+    [(GENESIS_ACCOUNT, 1_000_000), ("alice", 1000), ("bob", 500)]
+        .iter()
+        .map(|&(account, amount)| (account.to_string(), amount))
+        .collect()
 }
 
 impl Blockchain {
     pub fn new() -> Self {
-        let rps_config = crate::rps_mining::RPSMiningConfig::new();
-        let rps_miner = RPSMiner::new(rps_config);
-        
+        Self::new_with_genesis(default_genesis_allocations(), RPSMiningConfig::new())
+    }
+
+    /// A chain whose genesis block funds `allocations`, mined with `config`.
+    /// The genesis state root commits to the allocations, so different
+    /// allocations give a different genesis hash. Panics if the allocations
+    /// sum to more than a u128 holds.
+    pub fn new_with_genesis(allocations: BTreeMap<String, u128>, config: RPSMiningConfig) -> Self {
+        assert!(
+            allocations.values().try_fold(0u128, |sum, &amount| sum.checked_add(amount)).is_some(),
+            "Genesis allocations overflow total issuance"
+        );
+        let rps_miner = RPSMiner::new(config);
+
         let mut blockchain = Self {
             chain: Vec::new(),
             difficulty: 2,
             pending_transactions: VecDeque::new(),
+            queued_transactions: BTreeMap::new(),
             mining_reward: 100,
+            reward_schedule: BTreeMap::new(),
             system: SystemPallet::new(),
             balances: BalancesPallet::new(),
             rps_miner,
+            genesis_allocations: allocations,
+            reserved_senders: [NETWORK_ACCOUNT, GENESIS_ACCOUNT].iter().map(|name| name.to_string()).collect(),
+            max_transactions_per_block: 100,
+            halving_interval: 210_000,
+            hash_rate_window: 10,
+            max_mempool_size: 10_000,
+            pruned_state: None,
+            tx_index: HashMap::new(),
+            difficulty_history: Vec::new(),
         };
         
         // Create genesis block
@@ -38,132 +229,788 @@ impl Blockchain {
     }
 
     fn create_genesis_block(&mut self) {
-        let mut genesis = Block::genesis();
-        // Genesis block doesn't need RPS mining, just set a simple hash
-        genesis.hash = genesis.calculate_hash();
+        self.balances = self.genesis_balances();
+
+        // Genesis block doesn't need RPS mining, it just commits to the initial balances
+        let genesis = Block::genesis(Self::compute_state_root(&self.balances));
         self.chain.push(genesis);
-        
-        // Initialize some accounts with genesis balances
-        self.balances.set_balance(&"genesis".to_string(), 1_000_000);
-        self.balances.set_balance(&"alice".to_string(), 1000);
-        self.balances.set_balance(&"bob".to_string(), 500);
+    }
+
+    /// Accounts funded by the genesis block. An imported chain's allocations
+    /// may overflow total issuance; the ones that would are left out, and
+    /// the genesis state root then doesn't match, which `audit` reports.
+    fn genesis_balances(&self) -> BalancesPallet {
+        let mut balances = BalancesPallet::new();
+        for (account, &amount) in &self.genesis_allocations {
+            let _ = balances.set_balance(account, amount);
+        }
+        balances
     }
 
     pub fn get_latest_block(&self) -> &Block {
         self.chain.last().expect("Chain should have at least genesis block")
     }
 
-    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), String> {
-        if !transaction.is_valid() {
-            return Err("Invalid transaction".to_string());
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), BlockchainError> {
+        if transaction.is_expired_at(current_timestamp()) {
+            return Err(BlockchainError::TransactionExpired);
+        }
+        if self.reserved_senders.contains(&transaction.from) {
+            return Err(BlockchainError::ReservedSender(transaction.from));
+        }
+        // Coinbases are only ever created by mining
+        if !transaction.is_valid() || transaction.is_coinbase() {
+            return Err(BlockchainError::InvalidTransaction);
+        }
+
+        // A nonce ahead of the sender's next one waits in the queue until
+        // the transactions before it arrive
+        let sender = transaction.from.clone();
+        if transaction.nonce > self.get_next_nonce(&sender) {
+            let queued: usize = self.queued_transactions.values().map(|queue| queue.len()).sum();
+            if queued >= self.max_mempool_size {
+                return Err(BlockchainError::QueueFull);
+            }
+            self.queued_transactions
+                .entry(sender)
+                .or_default()
+                .insert(transaction.nonce, transaction);
+            return Ok(());
+        }
+
+        self.admit_to_mempool(transaction)?;
+        self.promote_queued(&sender);
+        Ok(())
+    }
+
+    /// Adds `transactions` in order, returning each one's result. Every
+    /// transaction is checked against the balances and nonces left by the
+    /// ones accepted before it, so a sender's self-consistent run of nonces is
+    /// accepted as a whole. A rejection doesn't stop the rest of the batch.
+    #[allow(dead_code)]
+    pub fn add_transactions(&mut self, transactions: Vec<Transaction>) -> Vec<Result<(), BlockchainError>> {
+        transactions.into_iter().map(|tx| self.add_transaction(tx)).collect()
+    }
+
+    /// Adds a transaction whose nonce is the sender's next one to the mempool
+    fn admit_to_mempool(&mut self, transaction: Transaction) -> Result<(), BlockchainError> {
+        // Check the sender can cover the amount and the fee on top of what
+        // their already-pending transactions will spend
+        let pending_spend = self.pending_spend(&transaction.from);
+        let projected_balance = self.balances.get_balance(&transaction.from).saturating_sub(pending_spend);
+        let total_cost = transaction
+            .amount
+            .checked_add(transaction.fee)
+            .ok_or(BlockchainError::Overflow("Amount plus fee"))?;
+        if projected_balance < total_cost {
+            return Err(BlockchainError::InsufficientBalance { have: projected_balance, need: total_cost });
         }
 
-        // Check if sender has sufficient balance
-        let sender_balance = self.balances.get_balance(&transaction.from);
-        if sender_balance < transaction.amount {
-            return Err("Insufficient balance".to_string());
+        // Check nonce, which follows on from the sender's pending transactions
+        let expected_nonce = self.get_next_nonce(&transaction.from);
+        if transaction.nonce != expected_nonce {
+            return Err(BlockchainError::InvalidNonce { expected: expected_nonce, got: transaction.nonce });
         }
 
-        // Check nonce
-        let expected_nonce = self.system.get_nonce(&transaction.from);
-        if transaction.nonce != expected_nonce + 1 {
-            return Err("Invalid nonce".to_string());
+        // A full mempool makes room by evicting its lowest-fee transaction,
+        // the most recent arrival among equals, but only for a higher fee.
+        // The sender's own transactions are left alone, as the new one can't
+        // run without them.
+        if self.mempool_is_full() {
+            let lowest = self
+                .pending_transactions
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, tx)| tx.from != transaction.from)
+                .min_by_key(|(_, tx)| tx.fee)
+                .map(|(index, tx)| (index, tx.fee));
+            match lowest {
+                Some((index, fee)) if fee < transaction.fee => {
+                    if let Some(evicted) = self.pending_transactions.remove(index) {
+                        self.requeue_after(&evicted.from, evicted.nonce);
+                    }
+                }
+                _ => return Err(BlockchainError::MempoolFull),
+            }
         }
 
         self.pending_transactions.push_back(transaction);
         Ok(())
     }
 
-    pub fn mine_pending_transactions(&mut self, mining_reward_address: String) -> Result<Block, String> {
-        // Always add a mining reward transaction, even if no other pending transactions
-        let reward_tx = Transaction::new(
-            "network".to_string(),
-            mining_reward_address.clone(),
-            self.mining_reward,
-            0
-        );
+    /// Moves `sender`'s queued transactions into the mempool for as long as
+    /// they carry on from its pending ones. A queued transaction that no
+    /// longer fits, e.g. for lack of balance, is dropped.
+    fn promote_queued(&mut self, sender: &str) {
+        let mut next_nonce = self.get_next_nonce(&sender.to_string());
+        let Some(queue) = self.queued_transactions.get_mut(sender) else {
+            return;
+        };
+        let mut runnable = Vec::new();
+        queue.retain(|&nonce, _| nonce >= next_nonce);
+        while let Some(tx) = queue.remove(&next_nonce) {
+            runnable.push(tx);
+            next_nonce += 1;
+        }
+        if queue.is_empty() {
+            self.queued_transactions.remove(sender);
+        }
 
-        let mut transactions = Vec::new();
-        transactions.push(reward_tx);
-
-        // Process any existing pending transactions
-        while let Some(tx) = self.pending_transactions.pop_front() {
-            // Execute the transaction
-            match self.balances.transfer(
-                tx.from.clone(),
-                tx.to.clone(),
-                tx.amount
-            ) {
-                Ok(_) => {
-                    self.system.inc_nonce(&tx.from);
-                    transactions.push(tx);
-                }
-                Err(e) => {
-                    println!("Transaction failed: {}", e);
-                    // Skip invalid transaction
-                }
+        for tx in runnable {
+            if tx.is_expired_at(current_timestamp()) || self.admit_to_mempool(tx).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Moves `sender`'s pending transactions after `nonce` back to the queue,
+    /// once the one with `nonce` has left the mempool unmined. They can't run
+    /// without it, and are promoted again when it's resubmitted.
+    fn requeue_after(&mut self, sender: &str, nonce: u32) {
+        let mut stranded = Vec::new();
+        self.pending_transactions.retain(|tx| {
+            let strand = tx.from == sender && tx.nonce > nonce;
+            if strand {
+                stranded.push(tx.clone());
             }
+            !strand
+        });
+        for tx in stranded {
+            self.queued_transactions
+                .entry(tx.from.clone())
+                .or_default()
+                .insert(tx.nonce, tx);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_queued_transaction_count(&self) -> usize {
+        self.queued_transactions.values().map(|queue| queue.len()).sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn mempool_is_full(&self) -> bool {
+        self.pending_transactions.len() >= self.max_mempool_size
+    }
+
+    /// Caps the mempool at `max_size` pending transactions
+    #[allow(dead_code)]
+    pub fn set_max_mempool_size(&mut self, max_size: usize) {
+        self.max_mempool_size = max_size;
+    }
+
+    /// Total amount plus fees `sender`'s pending transactions will spend
+    fn pending_spend(&self, sender: &str) -> u128 {
+        self.pending_transactions
+            .iter()
+            .filter(|tx| tx.from == sender)
+            .fold(0u128, |spend, tx| spend.saturating_add(tx.amount).saturating_add(tx.fee))
+    }
 
-            // Limit transactions per block
-            if transactions.len() >= 100 {
+    /// Takes up to `limit` transactions out of the mempool for the next
+    /// block, highest fee first and the earliest arrival among equals. Only a
+    /// sender's next transaction is ever eligible, so each sender's picks form
+    /// a contiguous run of nonces.
+    fn select_runnable_transactions(&mut self, limit: usize) -> Vec<Transaction> {
+        let system = &self.system;
+        self.pending_transactions.retain(|tx| tx.nonce > system.get_nonce(&tx.from));
+
+        let mut next_nonces: BTreeMap<String, u32> = BTreeMap::new();
+        let mut selected = Vec::new();
+        while selected.len() < limit {
+            let best = self
+                .pending_transactions
+                .iter()
+                .enumerate()
+                .filter(|(_, tx)| {
+                    let next = next_nonces.get(&tx.from).copied().unwrap_or_else(|| self.system.get_nonce(&tx.from) + 1);
+                    tx.nonce == next
+                })
+                .max_by_key(|(index, tx)| (tx.fee, std::cmp::Reverse(*index)))
+                .map(|(index, _)| index);
+            let Some(tx) = best.and_then(|index| self.pending_transactions.remove(index)) else {
                 break;
+            };
+            next_nonces.insert(tx.from.clone(), tx.nonce + 1);
+            selected.push(tx);
+        }
+        selected
+    }
+
+    pub fn mine_pending_transactions(&mut self, mining_reward_address: String) -> Result<Block, BlockchainError> {
+        self.mine_pending_transactions_with_progress(mining_reward_address, None)
+    }
+
+    /// Same as `mine_pending_transactions`, reporting each RPS mining round
+    /// to `progress`
+    pub fn mine_pending_transactions_with_progress(
+        &mut self,
+        mining_reward_address: String,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Block, BlockchainError> {
+        // Expired transactions are dropped without being mined, and the
+        // sender's later ones go back to the queue until the gap is refilled
+        let now = current_timestamp();
+        let mut expired = Vec::new();
+        self.pending_transactions.retain(|tx| {
+            let is_expired = tx.is_expired_at(now);
+            if is_expired {
+                expired.push((tx.from.clone(), tx.nonce));
+            }
+            !is_expired
+        });
+        for (sender, nonce) in expired {
+            self.requeue_after(&sender, nonce);
+        }
+
+        // Limit transactions per block, leaving room for the coinbase
+        let mut selected = self.select_runnable_transactions(self.max_transactions_per_block.saturating_sub(1));
+
+        // Order by sender and nonce so the block's contents, and so its
+        // merkle root, don't depend on the order transactions arrived in
+        selected.sort_by(|a, b| a.from.cmp(&b.from).then(a.nonce.cmp(&b.nonce)));
+
+        self.mine_selected_transactions(&selected, mining_reward_address, progress).inspect_err(|_| {
+            self.return_to_mempool(selected);
+        })
+    }
+
+    /// Builds and mines the next block from `selected`, only touching the
+    /// live state once the block has been mined
+    fn mine_selected_transactions(
+        &mut self,
+        selected: &[Transaction],
+        mining_reward_address: String,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Block, BlockchainError> {
+        // The block is applied to a copy of the state, which only replaces
+        // the live state once the block has been mined
+        let mut balances = self.balances.clone();
+        let mut system = self.system.clone();
+        let mut transactions = Vec::new();
+
+        // Process the selected pending transactions
+        for tx in selected.iter().cloned() {
+            // A sender's earlier transaction failing leaves a gap before this one
+            let outcome = if tx.nonce != system.get_nonce(&tx.from) + 1 {
+                Err("Nonce no longer follows on from the sender's last".to_string())
+            } else {
+                Self::apply_transaction(&mut balances, &mut system, &tx).map_err(|e| e.to_string())
+            };
+            // Skip the transaction, leaving a record of why in the block's events
+            match outcome {
+                Ok(()) => transactions.push(tx),
+                Err(reason) => system.deposit_event(Event::TransactionSkipped { from: tx.from, nonce: tx.nonce, reason }),
             }
         }
 
+        // Always add a mining reward transaction, even if no other pending
+        // transactions, paying the reward plus the fees of everything included
+        let fees: u128 = transactions.iter().map(|tx| tx.fee).sum();
+        let reward_tx = Transaction::coinbase(
+            mining_reward_address.clone(),
+            self.current_block_reward()
+                .checked_add(fees)
+                .ok_or(BlockchainError::Overflow("Mining reward plus fees"))?,
+        );
+        Self::apply_transaction(&mut balances, &mut system, &reward_tx)?;
+        transactions.insert(0, reward_tx);
+
         let previous_hash = self.get_latest_block().hash.clone();
         let mut new_block = Block::new(
             self.chain.len() as u32,
             transactions,
-            previous_hash
+            previous_hash,
+            Self::compute_state_root(&balances)
         );
 
+        let difficulty_score = self.get_rps_difficulty_info().difficulty_score();
+
         // Use RPS mining instead of traditional proof-of-work
-        match new_block.mine_block_rps(&mut self.rps_miner) {
+        match new_block.mine_block_rps(&mut self.rps_miner, progress) {
             Ok(_) => {
-                // Add mining reward to the miner's balance
-                let current_balance = self.balances.get_balance(&mining_reward_address);
-                self.balances.set_balance(
-                    &mining_reward_address,
-                    current_balance + self.mining_reward
-                );
+                // Retarget difficulty for the next block from this block's time
+                let block_time_ms = Self::block_time_ms(self.get_latest_block(), &new_block);
+                let target_ms = self.rps_miner.config.target_block_time_ms;
+                self.rps_miner.config.retarget(block_time_ms, target_ms);
+
+                self.balances = balances;
+                self.system = system;
 
                 // Increment block number
+                Self::deposit_block_mined(&mut self.system, &new_block);
                 self.system.inc_block_number(&mining_reward_address);
 
+                Self::index_transactions(&mut self.tx_index, &new_block);
+                self.difficulty_history.push((new_block.index, difficulty_score));
                 self.chain.push(new_block.clone());
                 Ok(new_block)
             }
-            Err(e) => Err(format!("RPS Mining failed: {}", e))
+            Err(e) => Err(e.into())
+        }
+    }
+
+    /// Puts the transactions taken for a block that failed to mine back at
+    /// the front of the mempool, then promotes whatever their senders had
+    /// queued behind them
+    fn return_to_mempool(&mut self, selected: Vec<Transaction>) {
+        let senders: BTreeSet<String> = selected.iter().map(|tx| tx.from.clone()).collect();
+        for tx in selected.into_iter().rev() {
+            self.pending_transactions.push_front(tx);
+        }
+        for sender in senders {
+            self.promote_queued(&sender);
         }
     }
 
     #[allow(dead_code)]
-    pub fn get_balance(&mut self, address: &String) -> u128 {
+    pub fn get_balance(&self, address: &String) -> u128 {
         self.balances.get_balance(address)
     }
 
+    /// Nonce of the sender's last mined transaction, 0 if it has sent none
+    #[allow(dead_code)]
+    pub fn get_nonce(&self, address: &String) -> u32 {
+        self.system.get_nonce(address)
+    }
+
+    /// The nonce the sender's next transaction must carry: the one after
+    /// the run of nonces waiting in the mempool that follows on from their
+    /// last mined one
+    #[allow(dead_code)]
+    pub fn get_next_nonce(&self, address: &String) -> u32 {
+        let pending: BTreeSet<u32> = self
+            .pending_transactions
+            .iter()
+            .filter(|tx| tx.from == *address)
+            .map(|tx| tx.nonce)
+            .collect();
+        let mut next_nonce = self.get_nonce(address) + 1;
+        while pending.contains(&next_nonce) {
+            next_nonce += 1;
+        }
+        next_nonce
+    }
+
+    /// Records that `block` was mined, once its transactions have been applied
+    fn deposit_block_mined(system: &mut SystemPallet, block: &Block) {
+        let games = block.rps_mining_result.as_ref().map_or(0, |result| result.total_games);
+        system.deposit_event(Event::BlockMined { index: block.index, games });
+    }
+
+    /// Applies one of a block's transactions to `balances` and `system`. The
+    /// reward from the network account is minted to its recipient; anything
+    /// else is a transfer that takes the fee from the sender and bumps their
+    /// nonce. The fee reaches the miner through the block's coinbase.
+    fn apply_transaction(
+        balances: &mut BalancesPallet,
+        system: &mut SystemPallet,
+        tx: &Transaction,
+    ) -> Result<(), BlockchainError> {
+        if tx.is_coinbase() {
+            balances.mint(&tx.to, tx.amount)?;
+            system.deposit_event(Event::Reward { to: tx.to.clone(), amount: tx.amount });
+        } else {
+            let total_cost = tx.amount.checked_add(tx.fee).ok_or(BlockchainError::Overflow("Amount plus fee"))?;
+            let have = balances.get_balance(&tx.from);
+            if have < total_cost {
+                return Err(BlockchainError::InsufficientBalance { have, need: total_cost });
+            }
+            balances.transfer(tx.from.clone(), tx.to.clone(), tx.amount)?;
+            balances.burn(&tx.from, tx.fee)?;
+            system.inc_nonce(&tx.from);
+            system.deposit_event(Event::Transfer { from: tx.from.clone(), to: tx.to.clone(), amount: tx.amount });
+        }
+        Ok(())
+    }
+
+    /// Reward minted by the next block to be mined
+    pub fn current_block_reward(&self) -> u128 {
+        self.block_reward_at(self.chain.len() as u32)
+    }
+
+    /// The base reward in force at `block_index`, halved once for every
+    /// `halving_interval` blocks before it
+    fn block_reward_at(&self, block_index: u32) -> u128 {
+        let halvings = block_index as u64 / self.halving_interval.max(1);
+        if halvings >= u128::BITS as u64 {
+            return 0;
+        }
+        let base = self
+            .reward_schedule
+            .range(..=block_index)
+            .next_back()
+            .map_or(self.mining_reward, |(_, &reward)| reward);
+        base >> halvings
+    }
+
+    #[allow(dead_code)]
+    pub fn get_mining_reward(&self) -> u128 {
+        self.mining_reward
+    }
+
+    /// Sets the base reward from the next block on. Blocks already mined
+    /// keep the reward they were paid, and still validate against it.
+    #[allow(dead_code)]
+    pub fn set_mining_reward(&mut self, reward: u128) {
+        if self.reward_schedule.is_empty() {
+            self.reward_schedule.insert(0, self.mining_reward);
+        }
+        self.reward_schedule.insert(self.chain.len() as u32, reward);
+        self.mining_reward = reward;
+    }
+
+    /// Halves the reward every `interval` blocks, at least 1
+    #[allow(dead_code)]
+    pub fn set_halving_interval(&mut self, interval: u64) {
+        self.halving_interval = interval.max(1);
+    }
+
+    /// Recomputes the sum of every balance and checks it against the pallet's
+    /// running total issuance
+    #[allow(dead_code)]
+    pub fn audit_supply(&self) -> Result<(), String> {
+        let sum = self
+            .balances
+            .balances
+            .values()
+            .try_fold(0u128, |sum, balance| sum.checked_add(*balance))
+            .ok_or("Sum of balances overflows")?;
+        if sum != self.balances.total_issuance() {
+            return Err(format!(
+                "Balances sum to {} but total issuance is {}",
+                sum,
+                self.balances.total_issuance()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fees paid by the block's transactions, all of which went to its miner
+    #[allow(dead_code)]
+    pub fn get_total_fees_in_block(block: &Block) -> u128 {
+        block
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .map(|tx| tx.fee)
+            .sum()
+    }
+
+    fn block_time_ms(previous_block: &Block, block: &Block) -> u128 {
+        block.timestamp.saturating_sub(previous_block.timestamp) as u128 * 1000
+    }
+
     #[allow(dead_code)]
     pub fn is_chain_valid(&self) -> bool {
+        self.audit().is_empty()
+    }
+
+    /// Replays block `block_index`'s RPS games at the difficulty it was mined
+    /// at, without checking the rest of the chain
+    #[allow(dead_code)]
+    pub fn verify_block_mining(&self, block_index: u32) -> bool {
+        let Some(block) = self.get_block_by_index(block_index) else {
+            return false;
+        };
+        match block.rps_mining_result {
+            Some(ref rps_result) => RPSMiner::verify_result(
+                &block.rps_block_data(),
+                rps_result,
+                &self.rps_config_at(block_index),
+                self.rps_miner.blockchain_seed,
+            ),
+            None => false,
+        }
+    }
+
+    /// The mining config as it was when block `block_index` was mined,
+    /// replayed from genesis
+    fn rps_config_at(&self, block_index: u32) -> RPSMiningConfig {
+        let mut rps_config = RPSMiningConfig {
+            blocks_mined: 0,
+            difficulty_adjustment: 0,
+            recent_block_times_ms: VecDeque::new(),
+            ..self.rps_miner.config.clone()
+        };
+
+        let mined_before = (block_index as usize).min(self.chain.len()).saturating_sub(1);
+        for window in self.chain[..=mined_before].windows(2) {
+            rps_config.blocks_mined += 1;
+            let target_ms = rps_config.target_block_time_ms;
+            rps_config.retarget(Self::block_time_ms(&window[0], &window[1]), target_ms);
+        }
+        rps_config
+    }
+
+    /// Removes the latest block and undoes it: its transfers are reversed,
+    /// fees refunded, the coinbase burned, sender nonces decremented and the
+    /// mining difficulty restored to what it was before the block was mined.
+    /// The genesis block can't be rolled back.
+    #[allow(dead_code)]
+    pub fn rollback_last_block(&mut self) -> Result<Block, String> {
+        if self.chain.len() <= 1 {
+            return Err("Cannot roll back the genesis block".to_string());
+        }
+        let block = self.get_latest_block().clone();
+        if block.is_pruned() {
+            return Err("Cannot roll back a pruned block".to_string());
+        }
+
+        // Undo on copies so a failure leaves the live state untouched
+        let mut balances = self.balances.clone();
+        let mut system = self.system.clone();
+        for tx in block.transactions.iter().rev() {
+            if tx.is_coinbase() {
+                balances.burn(&tx.to, tx.amount)?;
+            } else {
+                balances.transfer(tx.to.clone(), tx.from.clone(), tx.amount)?;
+                balances.mint(&tx.from, tx.fee)?;
+                system.dec_nonce(&tx.from);
+            }
+        }
+        let miner = block.transactions.first().map(|tx| tx.to.clone()).unwrap_or_default();
+        system.dec_block_number(&miner);
+
+        // Chains saved before emptied accounts were removed may still hold
+        // zero balances, which the undo can't restore, so if it doesn't land
+        // on the previous state root rebuild the state from the chain instead
+        let previous_block = &self.chain[self.chain.len() - 2];
+        if Self::compute_state_root(&balances) != previous_block.state_root {
+            (balances, system) = self.replay_state(&self.chain[..self.chain.len() - 1])?;
+        }
+
+        self.rps_miner.config = self.rps_config_at(block.index);
+        self.balances = balances;
+        self.system = system;
+        self.chain.pop();
+        self.tx_index.retain(|_, &mut (block_index, _)| block_index != block.index);
+        self.difficulty_history.pop();
+        Ok(block)
+    }
+
+    /// Balances derived from the genesis allocations by replaying every
+    /// transaction in the chain, to compare against the stored balances.
+    /// Transactions that can't be applied are skipped, as `audit` reports them.
+    #[allow(dead_code)]
+    pub fn recompute_balances(&self) -> BalancesPallet {
+        let (mut balances, mut system, blocks) = self.replay_start(&self.chain);
+        for tx in blocks.iter().flat_map(Self::execution_order) {
+            let _ = Self::apply_transaction(&mut balances, &mut system, tx);
+        }
+        balances
+    }
+
+    /// Where replaying `chain` starts: the pruned state and the blocks after
+    /// it if `chain` is pruned up to there, otherwise the genesis balances
+    /// and every block after genesis
+    fn replay_start<'a>(&self, chain: &'a [Block]) -> (BalancesPallet, SystemPallet, &'a [Block]) {
+        if let Some(ref pruned) = self.pruned_state {
+            let through = pruned.through as usize;
+            if through < chain.len() && chain[1..=through].iter().all(Block::is_pruned) {
+                return (pruned.balances.clone(), pruned.system.clone(), &chain[through + 1..]);
+            }
+        }
+        (self.genesis_balances(), SystemPallet::new(), chain.get(1..).unwrap_or_default())
+    }
+
+    /// A block's transactions in the order mining executed them: the
+    /// coinbase comes first in the block but is applied last, once the fees
+    /// are known, so a miner can't spend a reward in the block that pays it
+    fn execution_order(block: &Block) -> impl Iterator<Item = &Transaction> {
+        block.transactions.iter().skip(1).chain(block.transactions.first())
+    }
+
+    /// Account state after applying every block of `chain` to the genesis
+    /// balances, or to the pruned state for a pruned chain
+    fn replay_state(&self, chain: &[Block]) -> Result<(BalancesPallet, SystemPallet), String> {
+        let (mut balances, mut system, blocks) = self.replay_start(chain);
+        for block in blocks {
+            for tx in Self::execution_order(block) {
+                Self::apply_transaction(&mut balances, &mut system, tx)
+                    .map_err(|e| format!("Block {}: transaction {} can't be applied: {}", block.index, tx.hash, e))?;
+            }
+            let miner = block.transactions.first().map(|tx| tx.to.clone()).unwrap_or_default();
+            Self::deposit_block_mined(&mut system, block);
+            system.inc_block_number(&miner);
+        }
+        Ok((balances, system))
+    }
+
+    /// Switches to `candidate` if it is longer than the current chain, shares
+    /// its genesis block and passes the same checks as `is_chain_valid`.
+    /// Balances are re-derived by replaying the candidate's transactions.
+    /// Returns `Ok(false)` if the candidate isn't longer, and an error if it
+    /// is longer but invalid.
+    #[allow(dead_code)]
+    pub fn try_replace_chain(&mut self, candidate: Vec<Block>) -> Result<bool, String> {
+        if candidate.len() <= self.chain.len() {
+            return Ok(false);
+        }
+        if candidate[0].hash != self.chain[0].hash {
+            return Err("Candidate chain has a different genesis block".to_string());
+        }
+
+        let (balances, system) = self.replay_state(&candidate)?;
+        let mut replacement = Blockchain {
+            chain: candidate,
+            difficulty: self.difficulty,
+            pending_transactions: VecDeque::new(),
+            queued_transactions: BTreeMap::new(),
+            mining_reward: self.mining_reward,
+            reward_schedule: self.reward_schedule.clone(),
+            system,
+            balances,
+            rps_miner: self.rps_miner.clone(),
+            genesis_allocations: self.genesis_allocations.clone(),
+            reserved_senders: self.reserved_senders.clone(),
+            max_transactions_per_block: self.max_transactions_per_block,
+            halving_interval: self.halving_interval,
+            hash_rate_window: self.hash_rate_window,
+            max_mempool_size: self.max_mempool_size,
+            pruned_state: self.pruned_state.clone(),
+            tx_index: HashMap::new(),
+            difficulty_history: Vec::new(),
+        };
+        replacement.rebuild_transaction_index();
+        replacement.rebuild_difficulty_history();
+        replacement.rps_miner.config = replacement.rps_config_at(replacement.chain.len() as u32);
+
+        if let Some(finding) = replacement.audit().first() {
+            return Err(format!("Candidate block {} is invalid: {}", finding.block_index, finding.reason));
+        }
+
+        // Only now that the candidate is adopted do the pools move over.
+        // Queued transactions are ahead of their sender's next nonce on
+        // either chain, and promote as usual once the gaps fill.
+        replacement.queued_transactions = std::mem::take(&mut self.queued_transactions);
+
+        // Drop pending transactions the new chain already includes
+        let included: BTreeSet<Hash> = replacement
+            .chain
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.hash.clone()))
+            .collect();
+        replacement.pending_transactions = std::mem::take(&mut self.pending_transactions)
+            .into_iter()
+            .filter(|tx| !included.contains(&tx.hash))
+            .collect();
+
+        *self = replacement;
+        Ok(true)
+    }
+
+    /// Checks every block and reports each problem found, rather than
+    /// stopping at the first one. An empty report means the chain is valid.
+    pub fn audit(&self) -> Vec<AuditFinding> {
+        let mut findings = Vec::new();
+        let mut flag = |block_index: u32, check: AuditCheck, reason: String| {
+            findings.push(AuditFinding { block_index, check, reason });
+        };
+
+        // Difficulty is replayed from genesis alongside the chain
+        let mut rps_config = RPSMiningConfig {
+            blocks_mined: 0,
+            difficulty_adjustment: 0,
+            recent_block_times_ms: VecDeque::new(),
+            ..self.rps_miner.config.clone()
+        };
+
+        // So is the account state, which each block's state root must match.
+        // Pruned blocks have no transactions left to replay, so it picks up
+        // from the pruned state instead.
+        let (mut balances, mut system, unpruned) = self.replay_start(&self.chain);
+        let pruned_through = self.chain.len() - 1 - unpruned.len();
+        if self.chain[0].state_root != Self::compute_state_root(&self.genesis_balances()) {
+            flag(0, AuditCheck::StateRoot, "Genesis state root doesn't match the genesis balances".to_string());
+        }
+
         for i in 1..self.chain.len() {
             let current_block = &self.chain[i];
             let previous_block = &self.chain[i - 1];
+            let index = current_block.index;
+
+            if current_block.previous_hash != previous_block.hash {
+                flag(index, AuditCheck::Linkage, format!("Previous hash doesn't match block {}'s hash", previous_block.index));
+            }
+            if current_block.index != previous_block.index + 1 {
+                flag(index, AuditCheck::Linkage, format!("Index doesn't follow block {}", previous_block.index));
+            }
+            if current_block.hash != current_block.calculate_hash() {
+                flag(index, AuditCheck::BlockHash, "Stored hash doesn't match the block's contents".to_string());
+            }
 
-            if !current_block.is_valid(Some(previous_block)) {
-                return false;
+            // Only the header of a pruned block is left to check
+            let pruned = i <= pruned_through;
+            if !pruned && current_block.merkle_root != Block::calculate_merkle_root(&current_block.transactions) {
+                flag(index, AuditCheck::MerkleRoot, "Merkle root doesn't match the block's transactions".to_string());
+            }
+            for tx in current_block.transactions.iter().filter(|tx| !tx.is_well_formed()) {
+                flag(index, AuditCheck::Transaction, format!("Transaction {} is malformed", tx.hash));
             }
 
             // Check RPS mining proof instead of traditional proof of work
-            if let Some(ref rps_result) = current_block.rps_mining_result {
-                if !rps_result.success {
-                    return false;
+            match current_block.rps_mining_result {
+                // Replay the games at the difficulty this block was mined at
+                Some(ref rps_result) => {
+                    let blockchain_seed = self.rps_miner.blockchain_seed;
+                    if !RPSMiner::verify_result(&current_block.rps_block_data(), rps_result, &rps_config, blockchain_seed) {
+                        flag(index, AuditCheck::RpsProof, "RPS mining result doesn't replay".to_string());
+                    }
                 }
-                // Additional validation could be added here to verify RPS mining
-            } else if i > 0 {
                 // Non-genesis blocks should have RPS mining results
-                return false;
+                None => flag(index, AuditCheck::RpsProof, "Block has no RPS mining result".to_string()),
+            }
+
+            if !pruned {
+                let coinbases: Vec<&Transaction> = current_block
+                    .transactions
+                    .iter()
+                    .filter(|tx| tx.is_coinbase())
+                    .collect();
+                let expected_coinbase = self.block_reward_at(index).saturating_add(Self::get_total_fees_in_block(current_block));
+                match coinbases.as_slice() {
+                    [coinbase] => {
+                        if coinbase.amount != expected_coinbase {
+                            flag(
+                                index,
+                                AuditCheck::Coinbase,
+                                format!("Coinbase pays {} instead of {}", coinbase.amount, expected_coinbase),
+                            );
+                        }
+                        // Replaying applies the first transaction last and
+                        // credits its recipient with the block, as the coinbase
+                        if !current_block.transactions[0].is_coinbase() {
+                            flag(index, AuditCheck::Coinbase, "Coinbase isn't the block's first transaction".to_string());
+                        }
+                    }
+                    _ => flag(index, AuditCheck::Coinbase, format!("Expected one coinbase, found {}", coinbases.len())),
+                }
+
+                for tx in Self::execution_order(current_block) {
+                    if let Err(e) = Self::apply_transaction(&mut balances, &mut system, tx) {
+                        flag(index, AuditCheck::StateRoot, format!("Transaction {} can't be applied: {}", tx.hash, e));
+                    }
+                }
+            }
+            // The last pruned block's state root has to match the pruned state
+            if i >= pruned_through && current_block.state_root != Self::compute_state_root(&balances) {
+                flag(index, AuditCheck::StateRoot, "State root doesn't match the replayed balances".to_string());
             }
+
+            rps_config.blocks_mined += 1;
+            let target_ms = rps_config.target_block_time_ms;
+            rps_config.retarget(Self::block_time_ms(previous_block, current_block), target_ms);
+        }
+
+        // The live balances must be the state the chain ends in
+        let latest_block = self.get_latest_block();
+        if Self::compute_state_root(&self.balances) != latest_block.state_root {
+            flag(latest_block.index, AuditCheck::StateRoot, "Live balances don't match the latest state root".to_string());
         }
-        true
+
+        findings
     }
 
     #[allow(dead_code)]
@@ -181,8 +1028,20 @@ impl Blockchain {
         history
     }
 
+    /// Looks `tx_hash` up in the transaction index. `chain` can be changed
+    /// directly, leaving the index behind, so a hash the index is missing or
+    /// has a stale entry for falls back to scanning every block.
     #[allow(dead_code)]
     pub fn find_transaction(&self, tx_hash: &Hash) -> Option<(&Block, &Transaction, usize)> {
+        let indexed = self.tx_index.get(tx_hash).and_then(|&(block_index, position)| {
+            let block = self.chain.get(block_index as usize)?;
+            let tx = block.transactions.get(position)?;
+            (tx.hash == *tx_hash).then_some((block, tx, position))
+        });
+        indexed.or_else(|| self.scan_for_transaction(tx_hash))
+    }
+
+    fn scan_for_transaction(&self, tx_hash: &Hash) -> Option<(&Block, &Transaction, usize)> {
         for block in &self.chain {
             for (index, tx) in block.transactions.iter().enumerate() {
                 if tx.hash == *tx_hash {
@@ -193,6 +1052,27 @@ impl Blockchain {
         None
     }
 
+    #[allow(dead_code)]
+    pub fn contains_transaction(&self, tx_hash: &Hash) -> bool {
+        self.find_transaction(tx_hash).is_some()
+    }
+
+    /// Adds `block`'s transactions to `index`. A hash already there keeps
+    /// its earlier position, as a scan from genesis would find.
+    fn index_transactions(index: &mut HashMap<Hash, (u32, usize)>, block: &Block) {
+        for (position, tx) in block.transactions.iter().enumerate() {
+            index.entry(tx.hash.clone()).or_insert((block.index, position));
+        }
+    }
+
+    fn rebuild_transaction_index(&mut self) {
+        let mut index = HashMap::new();
+        for block in &self.chain {
+            Self::index_transactions(&mut index, block);
+        }
+        self.tx_index = index;
+    }
+
     #[allow(dead_code)]
     pub fn get_transaction_proof(&self, tx_hash: &Hash) -> Option<(Vec<Hash>, usize, u32)> {
         if let Some((block, _tx, tx_index)) = self.find_transaction(tx_hash) {
@@ -205,54 +1085,187 @@ impl Blockchain {
 
     #[allow(dead_code)]
     pub fn verify_transaction_proof(&self, tx: &Transaction, proof: &[Hash], tx_index: usize, block_index: u32) -> bool {
-        if let Some(block) = self.chain.get(block_index as usize) {
-            return block.verify_transaction_inclusion(tx, proof, tx_index);
+        // Checked against the block's stored root, no need to rebuild its tree
+        match self.chain.get(block_index as usize) {
+            Some(block) => verify_proof_against_root(&tx.hash, proof, tx_index, &block.merkle_root),
+            None => false,
         }
-        false
     }
 
+    /// Everything each reward address has been paid by coinbase transactions,
+    /// fees included, highest first and by address among equals
     #[allow(dead_code)]
-    pub fn get_block_by_index(&self, index: u32) -> Option<&Block> {
-        self.chain.get(index as usize)
+    pub fn get_miner_leaderboard(&self) -> Vec<(String, u128)> {
+        let mut totals: BTreeMap<String, u128> = BTreeMap::new();
+        for block in self.non_genesis_blocks() {
+            if let Some(coinbase) = block.transactions.first().filter(|tx| tx.is_coinbase()) {
+                let total = totals.entry(coinbase.to.clone()).or_default();
+                *total = total.saturating_add(coinbase.amount);
+            }
+        }
+
+        let mut leaderboard: Vec<(String, u128)> = totals.into_iter().collect();
+        // Stable, so equal totals stay in address order
+        leaderboard.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+        leaderboard
     }
 
+    /// How many blocks `miner` has been paid the reward for
     #[allow(dead_code)]
-    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<&Block> {
-        self.chain.iter().find(|block| block.hash == *hash)
+    pub fn blocks_mined_by(&self, miner: &String) -> u32 {
+        self.system.blocks_mined_by(miner)
     }
 
-    pub fn get_chain_length(&self) -> usize {
-        self.chain.len()
+    /// Events deposited while block `index` was executed, in order
+    #[allow(dead_code)]
+    pub fn get_block_events(&self, index: u32) -> &[Event] {
+        self.system.get_events(index)
+    }
+
+    /// Every block, genesis first
+    pub fn blocks(&self) -> impl ExactSizeIterator<Item = &Block> {
+        self.chain.iter()
+    }
+
+    /// Every mined block, i.e. all but genesis
+    pub fn non_genesis_blocks(&self) -> impl ExactSizeIterator<Item = &Block> {
+        self.blocks().skip(1)
     }
 
+    /// Blocks with heights in `start..end`, clamped to the chain as in
+    /// `get_blocks_in_range`
     #[allow(dead_code)]
-    pub fn get_pending_transaction_count(&self) -> usize {
-        self.pending_transactions.len()
+    pub fn blocks_in_range(&self, start: u32, end: u32) -> impl ExactSizeIterator<Item = &Block> {
+        self.get_blocks_in_range(start, end).iter()
     }
 
     #[allow(dead_code)]
-    pub fn set_difficulty(&mut self, difficulty: usize) {
-        self.difficulty = difficulty;
+    pub fn get_block_by_index(&self, index: u32) -> Option<&Block> {
+        self.chain.get(index as usize)
     }
 
     #[allow(dead_code)]
-    pub fn get_network_hash_rate(&self) -> f64 {
-        if self.chain.len() < 2 {
+    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<&Block> {
+        self.blocks().find(|block| block.hash == *hash)
+    }
+
+    /// Blocks with indices in `from..to`, clamped to the chain
+    #[allow(dead_code)]
+    pub fn get_blocks_in_range(&self, from: u32, to: u32) -> &[Block] {
+        let end = (to as usize).min(self.chain.len());
+        let start = (from as usize).min(end);
+        &self.chain[start..end]
+    }
+
+    /// Drops the transactions of every block but the last `keep_last`,
+    /// keeping their headers, so the chain still links and validates but
+    /// can no longer prove what those blocks contained. The state they left
+    /// is kept for replays to start from. Pruned bodies can't come back, so
+    /// keeping more blocks than an earlier prune left does nothing.
+    #[allow(dead_code)]
+    pub fn prune(&mut self, keep_last: usize) {
+        let keep_from = self.chain.len().saturating_sub(keep_last);
+        // Bodies that are gone stay gone, so the pruned point only moves forward
+        let pruned_through = self.pruned_state.as_ref().map_or(0, |pruned| pruned.through as usize);
+        if keep_from <= pruned_through + 1 {
+            return;
+        }
+        // Leave a chain that doesn't replay as it is, for `audit` to report
+        let Ok((balances, system)) = self.replay_state(&self.chain[..keep_from]) else {
+            return;
+        };
+
+        for block in &mut self.chain[1..keep_from] {
+            block.transactions = Vec::new();
+        }
+        self.pruned_state = Some(PrunedState { through: keep_from as u32 - 1, balances, system });
+        self.rebuild_transaction_index();
+    }
+
+    /// The header chain, for clients that sync without transactions
+    #[allow(dead_code)]
+    pub fn get_headers(&self) -> Vec<BlockHeader> {
+        self.blocks().map(Block::header).collect()
+    }
+
+    pub fn get_chain_length(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// Replaces the set of accounts users may not send from
+    #[allow(dead_code)]
+    pub fn set_reserved_senders(&mut self, senders: BTreeSet<String>) {
+        self.reserved_senders = senders;
+    }
+
+    /// Transactions waiting to be mined, in arrival order
+    #[allow(dead_code)]
+    pub fn get_pending_transactions(&self) -> &VecDeque<Transaction> {
+        &self.pending_transactions
+    }
+
+    #[allow(dead_code)]
+    pub fn get_pending_transaction_count(&self) -> usize {
+        self.pending_transactions.len()
+    }
+
+    /// Caps the transactions per block, coinbase included. A block always has
+    /// room for its coinbase, so the cap is at least 1.
+    #[allow(dead_code)]
+    pub fn set_max_transactions_per_block(&mut self, max_transactions: usize) {
+        self.max_transactions_per_block = max_transactions.max(1);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_difficulty(&mut self, difficulty: usize) {
+        self.difficulty = difficulty;
+    }
+
+    /// Games per second over the last `hash_rate_window` blocks. Averaging
+    /// over a window keeps same-second or out-of-order timestamps from
+    /// producing zero or negative rates.
+    #[allow(dead_code)]
+    pub fn get_network_hash_rate(&self) -> f64 {
+        self.average_games_rate(self.hash_rate_window)
+    }
+
+    /// Number of recent blocks `get_network_hash_rate` averages over, at least 1
+    #[allow(dead_code)]
+    pub fn set_hash_rate_window(&mut self, window: usize) {
+        self.hash_rate_window = window.max(1);
+    }
+
+    /// Games per second over the last `window` mined blocks: the games played
+    /// in the window divided by the time it spans. Block timestamps only have
+    /// second resolution, so when the window spans no time at all the
+    /// recorded mining times are used instead.
+    #[allow(dead_code)]
+    pub fn average_games_rate(&self, window: usize) -> f64 {
+        let mined_blocks = self.chain.len().saturating_sub(1);
+        let window = window.min(mined_blocks);
+        if window == 0 {
             return 0.0;
         }
 
-        let latest_block = self.get_latest_block();
-        let prev_block = &self.chain[self.chain.len() - 2];
-        
-        let time_diff = (latest_block.timestamp - prev_block.timestamp) as f64;
-        
-        // For RPS mining, calculate "hash rate" based on games per second
-        if let Some(ref rps_result) = latest_block.rps_mining_result {
-            if time_diff > 0.0 {
-                rps_result.total_games as f64 / time_diff
-            } else {
-                0.0
-            }
+        let blocks = &self.chain[self.chain.len() - window..];
+        let (total_games, total_mining_ms) = blocks
+            .iter()
+            .filter_map(|block| block.rps_mining_result.as_ref())
+            .fold((0u64, 0u128), |(games, ms), result| {
+                (games + result.total_games, ms + result.mining_time_ms)
+            });
+
+        // The window starts where the block before it was sealed
+        let window_start = &self.chain[self.chain.len() - window - 1];
+        let elapsed_secs = self
+            .get_latest_block()
+            .timestamp
+            .saturating_sub(window_start.timestamp);
+
+        if elapsed_secs > 0 {
+            total_games as f64 / elapsed_secs as f64
+        } else if total_mining_ms > 0 {
+            total_games as f64 / (total_mining_ms as f64 / 1000.0)
         } else {
             0.0
         }
@@ -262,9 +1275,47 @@ impl Blockchain {
         self.rps_miner.get_difficulty_info()
     }
 
+    /// `(block_index, difficulty_score)` for every block after genesis, at
+    /// the difficulty it was mined at, oldest first
+    #[allow(dead_code)]
+    pub fn get_difficulty_history(&self) -> &[(u32, f64)] {
+        &self.difficulty_history
+    }
+
+    /// Replays the difficulty from genesis, as `rps_config_at` does, noting
+    /// the score each block was mined at
+    fn rebuild_difficulty_history(&mut self) {
+        // The config as it stood before the first block was mined
+        let mut rps_config = self.rps_config_at(0);
+        let mut history = Vec::with_capacity(self.chain.len().saturating_sub(1));
+        for window in self.chain.windows(2) {
+            history.push((window[1].index, rps_config.difficulty_info().difficulty_score()));
+            rps_config.blocks_mined += 1;
+            let target_ms = rps_config.target_block_time_ms;
+            rps_config.retarget(Self::block_time_ms(&window[0], &window[1]), target_ms);
+        }
+        self.difficulty_history = history;
+    }
+
+    /// PhlopCoin earned for the block `rps_result` mined: n / a^2, where a is
+    /// the games it took and n the fewest games the block's difficulty could
+    /// be met in (every player winning every game they play), capped at
+    /// `MAX_PHLOPCOIN_REWARD`
+    #[allow(dead_code)]
+    pub fn phlopcoin_reward(rps_result: &RPSMiningResult) -> f64 {
+        let min_games = (rps_result.total_required_wins as f64).max(1.0);
+        let actual_games = rps_result.total_games.max(1) as f64;
+
+        let reward = min_games / (actual_games * actual_games);
+        if reward.is_finite() {
+            reward.clamp(MIN_PHLOPCOIN_REWARD, MAX_PHLOPCOIN_REWARD)
+        } else {
+            MIN_PHLOPCOIN_REWARD
+        }
+    }
+
     pub fn get_total_rps_games(&self) -> u64 {
-        self.chain.iter()
-            .skip(1) // Skip genesis block
+        self.non_genesis_blocks()
             .map(|block| {
                 if let Some(ref rps_result) = block.rps_mining_result {
                     rps_result.total_games
@@ -277,10 +1328,14 @@ impl Blockchain {
 
     #[allow(dead_code)]
     pub fn create_state_merkle_tree(&self) -> FastMerkleTree {
+        Self::state_merkle_tree(&self.balances)
+    }
+
+    fn state_merkle_tree(balances: &BalancesPallet) -> FastMerkleTree {
         let mut tree = FastMerkleTree::new();
         
         // Add all account balances to the tree
-        for (account, balance) in &self.balances.balances {
+        for (account, balance) in &balances.balances {
             let state_data = format!("{}:{}", account, balance);
             tree.add_leaf(Hash::from_string(&state_data));
         }
@@ -289,11 +1344,74 @@ impl Blockchain {
         tree
     }
 
+    /// State root stored in block headers; an empty state gets `Hash::zero()`
+    fn compute_state_root(balances: &BalancesPallet) -> Hash {
+        Self::state_merkle_tree(balances)
+            .get_root()
+            .cloned()
+            .unwrap_or_else(Hash::zero)
+    }
+
+    /// Every account's balance, i.e. exactly what the state merkle tree's
+    /// leaves are built from
+    #[allow(dead_code)]
+    pub fn get_all_balances(&self) -> BTreeMap<String, u128> {
+        self.balances.balances.clone()
+    }
+
     #[allow(dead_code)]
     pub fn get_state_root(&self) -> Option<Hash> {
         let tree = self.create_state_merkle_tree();
         tree.get_root().cloned()
     }
+
+    /// The whole blockchain as pretty JSON, byte for byte the same every time
+    /// for the same chain: fields come out in declaration order, blocks and
+    /// the mempool in chain order, and every map is a `BTreeMap`, so accounts
+    /// and the like are sorted by key
+    #[allow(dead_code)]
+    pub fn export_json(&self) -> String {
+        // Nothing in a Blockchain can fail to serialize: all map keys are
+        // strings or integers and u128 is written out in full
+        serde_json::to_string_pretty(self).expect("Blockchain always serializes to JSON")
+    }
+
+    /// Reads a chain written by `export_json`, refusing it unless its
+    /// balances match its transactions and it passes `audit`
+    #[allow(dead_code)]
+    pub fn import_json(json: &str) -> Result<Self, BlockchainError> {
+        let mut blockchain: Self = serde_json::from_str(json).map_err(|e| BlockchainError::InvalidJson(e.to_string()))?;
+        blockchain.rebuild_transaction_index();
+        blockchain.rebuild_difficulty_history();
+
+        if blockchain.recompute_balances().balances != blockchain.balances.balances {
+            return Err(BlockchainError::BalanceMismatch);
+        }
+        blockchain.audit_supply().map_err(BlockchainError::SupplyMismatch)?;
+        if let Some(finding) = blockchain.audit().into_iter().next() {
+            return Err(BlockchainError::InvalidChain { block_index: finding.block_index, reason: finding.reason });
+        }
+        Ok(blockchain)
+    }
+
+    /// Writes `export_json` to `path`. The file is written under a temporary
+    /// name and renamed into place, so a crash never leaves a half-written
+    /// chain behind.
+    #[allow(dead_code)]
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, self.export_json())?;
+        fs::rename(&temp_path, path)
+    }
+
+    /// Reads a blockchain written by `save_to_file`, with the same checks as
+    /// `import_json`
+    #[allow(dead_code)]
+    pub fn load_from_file(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::import_json(&json).map_err(|e| format!("{}: {}", path.display(), e))
+    }
 }
 
 impl Default for Blockchain {
@@ -362,4 +1480,1331 @@ mod tests {
         let alice_history = blockchain.get_transaction_history(&"alice".to_string());
         assert!(!alice_history.is_empty());
     }
+
+    #[test]
+    fn test_average_games_rate() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(blockchain.average_games_rate(5), 0.0);
+
+        for _ in 0..4 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+
+        for window in [1, 3, 10] {
+            let rate = blockchain.average_games_rate(window);
+            assert!(rate.is_finite());
+            assert!(rate >= 0.0);
+        }
+        assert_eq!(blockchain.average_games_rate(0), 0.0);
+    }
+
+    #[test]
+    fn test_network_hash_rate_with_degenerate_timestamps() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(blockchain.get_network_hash_rate(), 0.0);
+
+        for _ in 0..3 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let games: u64 = blockchain.get_total_rps_games();
+
+        // Every block sealed in the same second falls back on mining time
+        let timestamp = blockchain.chain[0].timestamp;
+        for block in &mut blockchain.chain {
+            block.timestamp = timestamp;
+        }
+        let rate = blockchain.get_network_hash_rate();
+        assert!(rate.is_finite() && rate >= 0.0);
+
+        // Timestamps going backwards don't give a negative rate
+        let last = blockchain.chain.len() - 1;
+        blockchain.chain[last].timestamp = timestamp - 100;
+        let rate = blockchain.get_network_hash_rate();
+        assert!(rate.is_finite() && rate >= 0.0);
+
+        // A window spanning real time divides the summed games by it
+        for (i, block) in blockchain.chain.iter_mut().enumerate() {
+            block.timestamp = timestamp + 10 * i as u64;
+        }
+        assert_eq!(blockchain.get_network_hash_rate(), games as f64 / 30.0);
+        blockchain.set_hash_rate_window(1);
+        let latest_games = blockchain.chain[last].rps_mining_result.as_ref().unwrap().total_games;
+        assert_eq!(blockchain.get_network_hash_rate(), latest_games as f64 / 10.0);
+    }
+
+    #[test]
+    fn test_tampered_rps_result_invalidates_chain() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(blockchain.is_chain_valid());
+
+        // Forge a cheaper mining result and re-seal the block hash so only
+        // the RPS replay can catch it
+        let block = blockchain.chain.last_mut().unwrap();
+        block.rps_mining_result.as_mut().unwrap().total_games -= 1;
+        block.hash = block.calculate_hash();
+
+        assert!(!blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_phlopcoin_reward() {
+        let mut blockchain = Blockchain::new();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        let mut rps_result = block.rps_mining_result.unwrap();
+
+        // The first block needed a single win from each of its 100 players,
+        // and the reward goes by that even though the difficulty has moved on
+        assert_eq!(rps_result.total_required_wins, 100);
+        assert_ne!(blockchain.get_rps_difficulty_info().total_required_wins, 100);
+        rps_result.total_games = 100;
+        assert_eq!(Blockchain::phlopcoin_reward(&rps_result), 0.01);
+        rps_result.total_games = 400;
+        assert_eq!(Blockchain::phlopcoin_reward(&rps_result), 0.000625);
+    }
+
+    #[test]
+    fn test_phlopcoin_reward_is_bounded() {
+        let mut blockchain = Blockchain::new();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        let mut rps_result = block.rps_mining_result.unwrap();
+
+        for actual_games in [0, 1, 10] {
+            rps_result.total_games = actual_games;
+            let reward = Blockchain::phlopcoin_reward(&rps_result);
+            assert!(reward.is_finite());
+            assert!(reward <= MAX_PHLOPCOIN_REWARD);
+            assert!(reward >= MIN_PHLOPCOIN_REWARD);
+        }
+        rps_result.total_games = 0;
+        assert_eq!(Blockchain::phlopcoin_reward(&rps_result), MAX_PHLOPCOIN_REWARD);
+        rps_result.total_games = u64::MAX;
+        assert!(Blockchain::phlopcoin_reward(&rps_result) >= MIN_PHLOPCOIN_REWARD);
+    }
+
+    #[test]
+    fn test_get_blocks_in_range() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let indices = |blocks: &[Block]| blocks.iter().map(|block| block.index).collect::<Vec<_>>();
+        assert_eq!(indices(blockchain.get_blocks_in_range(0, 3)), vec![0, 1, 2]);
+        assert_eq!(indices(blockchain.get_blocks_in_range(1, 2)), vec![1]);
+        assert_eq!(indices(blockchain.get_blocks_in_range(2, 100)), vec![2]);
+        assert!(blockchain.get_blocks_in_range(2, 1).is_empty());
+        assert!(blockchain.get_blocks_in_range(5, 10).is_empty());
+    }
+
+    #[test]
+    fn test_reserved_senders_rejected() {
+        let mut blockchain = Blockchain::new();
+
+        let from_network = Transaction::new(NETWORK_ACCOUNT.to_string(), "mallory".to_string(), 1_000, 1);
+        assert_eq!(
+            blockchain.add_transaction(from_network),
+            Err(BlockchainError::ReservedSender(NETWORK_ACCOUNT.to_string()))
+        );
+        let from_genesis = Transaction::new(GENESIS_ACCOUNT.to_string(), "mallory".to_string(), 1_000, 1);
+        assert!(blockchain.add_transaction(from_genesis).is_err());
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+
+        // The coinbase still pays the miner
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions[0].from, NETWORK_ACCOUNT);
+        assert_eq!(blockchain.get_balance(&"miner".to_string()), blockchain.mining_reward);
+
+        // The reserved set is configurable
+        blockchain.set_reserved_senders(["alice".to_string()].into_iter().collect());
+        let from_alice = Transaction::new("alice".to_string(), "bob".to_string(), 10, 1);
+        assert!(blockchain.add_transaction(from_alice).is_err());
+    }
+
+    #[test]
+    fn test_state_root_committed_in_blocks() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 100, 1))
+            .unwrap();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        assert_eq!(Some(block.state_root.clone()), blockchain.get_state_root());
+        assert!(blockchain.is_chain_valid());
+
+        // A balance changed outside of any block no longer matches the chain
+        let mut tampered = blockchain.clone();
+        tampered.balances.set_balance("bob", 1_000_000).unwrap();
+        assert!(!tampered.is_chain_valid());
+
+        // Nor does a block header whose stored state root was rewritten
+        let mut tampered = blockchain.clone();
+        tampered.chain[1].state_root = Hash::from_string("forged state");
+        tampered.chain[1].hash = tampered.chain[1].calculate_hash();
+        assert!(!tampered.is_chain_valid());
+    }
+
+    #[test]
+    fn test_block_transaction_order_is_deterministic() {
+        let txs = [
+            Transaction::new("bob".to_string(), "carol".to_string(), 20, 1),
+            Transaction::new("alice".to_string(), "carol".to_string(), 10, 1),
+        ];
+
+        let mine_in_order = |order: &[usize]| {
+            let mut blockchain = Blockchain::new();
+            for &i in order {
+                blockchain.add_transaction(txs[i].clone()).unwrap();
+            }
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap()
+        };
+
+        let first = mine_in_order(&[0, 1]);
+        let second = mine_in_order(&[1, 0]);
+
+        let senders: Vec<&str> = first.transactions[1..].iter().map(|tx| tx.from.as_str()).collect();
+        assert_eq!(senders, vec!["alice", "bob"]);
+        assert_eq!(first.transactions[1..], second.transactions[1..]);
+
+        // With the same coinbase both blocks have the same merkle root
+        let mut transactions = vec![first.transactions[0].clone()];
+        transactions.extend_from_slice(&second.transactions[1..]);
+        let rebuilt = Block::new(1, transactions, first.previous_hash.clone(), first.state_root.clone());
+        assert_eq!(rebuilt.merkle_root, first.merkle_root);
+    }
+
+    #[test]
+    fn test_audit_flags_only_the_corrupted_block() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..3 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        assert!(blockchain.audit().is_empty());
+
+        blockchain.chain[2].merkle_root = Hash::from_string("corrupted");
+
+        let findings = blockchain.audit();
+        assert!(!findings.is_empty());
+        assert!(findings.iter().all(|finding| finding.block_index == 2));
+        assert!(findings.iter().any(|finding| finding.check == AuditCheck::MerkleRoot));
+        assert!(!blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_block_needs_exactly_one_coinbase() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 100, 1))
+            .unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(blockchain.is_chain_valid());
+
+        // Reseal block 1 with its transactions edited, so only the coinbase
+        // check has anything to object to in the block itself
+        let forge = |edit: &dyn Fn(&mut Vec<Transaction>)| {
+            let mut forged = blockchain.clone();
+            let block = &mut forged.chain[1];
+            edit(&mut block.transactions);
+            block.merkle_root = Block::calculate_merkle_root(&block.transactions);
+            block.hash = block.calculate_hash();
+            forged
+                .audit()
+                .into_iter()
+                .filter(|finding| finding.check == AuditCheck::Coinbase)
+                .map(|finding| (finding.block_index, finding.reason))
+                .collect::<Vec<_>>()
+        };
+
+        let no_reward = forge(&|transactions| {
+            transactions.remove(0);
+        });
+        assert_eq!(no_reward, vec![(1, "Expected one coinbase, found 0".to_string())]);
+
+        let double_reward = forge(&|transactions| {
+            let coinbase = transactions[0].clone();
+            transactions.push(coinbase);
+        });
+        assert_eq!(double_reward, vec![(1, "Expected one coinbase, found 2".to_string())]);
+
+        let expected = blockchain.block_reward_at(1) + Blockchain::get_total_fees_in_block(&blockchain.chain[1]);
+        let inflated = forge(&|transactions| {
+            transactions[0] = Transaction::coinbase("miner".to_string(), expected + 1);
+        });
+        assert_eq!(
+            inflated,
+            vec![(1, format!("Coinbase pays {} instead of {}", expected + 1, expected))]
+        );
+
+        let moved = forge(&|transactions| {
+            transactions.swap(0, 1);
+        });
+        assert_eq!(moved, vec![(1, "Coinbase isn't the block's first transaction".to_string())]);
+    }
+
+    #[test]
+    fn test_audit_applies_the_coinbase_last() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        // Have the miner spend its reward inside the block that pays it, with
+        // a state root that only holds if the coinbase were applied first
+        let mut balances = blockchain.genesis_balances();
+        let mut system = SystemPallet::new();
+        let block = &mut blockchain.chain[1];
+        block.transactions.push(Transaction::new("miner".to_string(), "bob".to_string(), 50, 1));
+        for tx in &block.transactions {
+            Blockchain::apply_transaction(&mut balances, &mut system, tx).unwrap();
+        }
+        block.merkle_root = Block::calculate_merkle_root(&block.transactions);
+        block.state_root = Blockchain::compute_state_root(&balances);
+        block.hash = block.calculate_hash();
+        blockchain.balances = balances;
+
+        // Mining would never have run the spend, so neither does the audit
+        assert!(blockchain.replay_state(&blockchain.chain).is_err());
+        assert!(blockchain.audit().iter().any(|finding| {
+            finding.block_index == 1
+                && finding.check == AuditCheck::StateRoot
+                && finding.reason.contains("can't be applied")
+        }));
+    }
+
+    #[test]
+    fn test_tampered_serialized_block_rejected() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..3 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        assert!((1..=3).all(|index| blockchain.verify_block_mining(index)));
+
+        // Claim block 2 took a single game, as if edited in a saved chain
+        let mut json = serde_json::to_value(&blockchain.chain[2]).unwrap();
+        json["rps_mining_result"]["total_games"] = serde_json::json!(1);
+        let mut forged: Block = serde_json::from_value(json).unwrap();
+        forged.hash = forged.calculate_hash();
+        blockchain.chain[2] = forged;
+
+        assert!(!blockchain.verify_block_mining(2));
+        assert!(blockchain.verify_block_mining(1));
+        assert!(!blockchain.is_chain_valid());
+        assert!(blockchain
+            .audit()
+            .iter()
+            .any(|finding| finding.block_index == 2 && finding.check == AuditCheck::RpsProof));
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let path = std::env::temp_dir().join(format!("phlopchain_chain_{}.json", std::process::id()));
+
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 100, 1))
+            .unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.save_to_file(&path).unwrap();
+
+        let mut loaded = Blockchain::load_from_file(&path).unwrap();
+        assert_eq!(loaded.get_chain_length(), 3);
+        assert_eq!(loaded.get_latest_block().hash, blockchain.get_latest_block().hash);
+        assert_eq!(loaded.get_balance(&"bob".to_string()), 600);
+
+        // Mining carries on from the loaded state
+        loaded.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(loaded.is_chain_valid());
+
+        // A tampered file is refused
+        blockchain.balances.set_balance("bob", 1_000_000).unwrap();
+        blockchain.save_to_file(&path).unwrap();
+        assert!(Blockchain::load_from_file(&path).is_err());
+
+        let _ = std::fs::remove_file(&path);
+        assert!(Blockchain::load_from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_fees_paid_to_miner() {
+        let mut blockchain = Blockchain::new();
+        let tx = Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 100, 5, 1);
+        blockchain.add_transaction(tx).unwrap();
+
+        // The fee has to be covered on top of the amount
+        let too_costly = Transaction::new_with_fee("bob".to_string(), "alice".to_string(), 500, 1, 1);
+        assert_eq!(
+            blockchain.add_transaction(too_costly),
+            Err(BlockchainError::InsufficientBalance { have: 500, need: 501 })
+        );
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(Blockchain::get_total_fees_in_block(&block), 5);
+        assert_eq!(block.transactions[0].from, NETWORK_ACCOUNT);
+        assert_eq!(block.transactions[0].amount, blockchain.mining_reward + 5);
+
+        assert_eq!(blockchain.get_balance(&"alice".to_string()), 895);
+        assert_eq!(blockchain.get_balance(&"bob".to_string()), 600);
+        assert_eq!(blockchain.get_balance(&"miner".to_string()), blockchain.mining_reward + 5);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_highest_fees_mined_first() {
+        let mut blockchain = Blockchain::new();
+        for nonce in 1..=99 {
+            let tx = Transaction::new("alice".to_string(), "carol".to_string(), 1, nonce);
+            blockchain.pending_transactions.push_back(tx);
+        }
+        let priority = Transaction::new_with_fee("bob".to_string(), "carol".to_string(), 1, 10, 1);
+        blockchain.add_transaction(priority.clone()).unwrap();
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 100);
+        assert!(block.transactions.contains(&priority));
+
+        // The last zero-fee transaction to arrive waits for the next block
+        assert_eq!(blockchain.pending_transactions.len(), 1);
+        assert_eq!(blockchain.pending_transactions[0].nonce, 99);
+    }
+
+    #[test]
+    fn test_max_transactions_per_block() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_max_transactions_per_block(3);
+        for nonce in 1..=4 {
+            let tx = Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce);
+            blockchain.pending_transactions.push_back(tx);
+        }
+
+        // The coinbase takes one of the three slots
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(blockchain.get_pending_transaction_count(), 2);
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+        assert_eq!(blockchain.get_balance(&"bob".to_string()), 540);
+
+        // Even a cap of zero leaves room for the coinbase
+        blockchain.set_max_transactions_per_block(0);
+        assert_eq!(blockchain.max_transactions_per_block, 1);
+    }
+
+    #[test]
+    fn test_pending_double_spend_rejected() {
+        let mut blockchain = Blockchain::new();
+
+        // Each transfer is 60% of alice's 1000, so only one of them fits
+        let first = Transaction::new("alice".to_string(), "bob".to_string(), 600, 1);
+        let second = Transaction::new("alice".to_string(), "carol".to_string(), 600, 2);
+        blockchain.add_transaction(first).unwrap();
+        assert_eq!(
+            blockchain.add_transaction(second),
+            Err(BlockchainError::InsufficientBalance { have: 400, need: 600 })
+        );
+
+        // What's left over can still be spent, at the next nonce
+        let rest = Transaction::new("alice".to_string(), "carol".to_string(), 400, 2);
+        blockchain.add_transaction(rest).unwrap();
+        assert_eq!(blockchain.get_pending_transaction_count(), 2);
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(blockchain.get_balance(&"alice".to_string()), 0);
+    }
+
+    #[test]
+    fn test_get_balance_through_shared_reference() {
+        let blockchain = Blockchain::new();
+        let readers = [&blockchain, &blockchain];
+        let total: u128 = readers.iter().map(|chain| chain.get_balance(&"alice".to_string())).sum();
+        assert_eq!(total, 2000);
+    }
+
+    #[test]
+    fn test_supply_matches_issuance() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(blockchain.balances.total_issuance(), 1_001_500);
+        assert_eq!(blockchain.audit_supply(), Ok(()));
+
+        let tx = Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 100, 7, 1);
+        blockchain.add_transaction(tx).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        // The fee is burned from the sender and minted again in the coinbase,
+        // so only the reward adds to the supply
+        assert_eq!(blockchain.balances.total_issuance(), 1_001_500 + blockchain.mining_reward);
+        assert_eq!(blockchain.audit_supply(), Ok(()));
+
+        blockchain.balances.burn("bob", 50).unwrap();
+        blockchain.balances.set_balance("carol", 25).unwrap();
+        assert_eq!(blockchain.balances.total_issuance(), 1_001_475 + blockchain.mining_reward);
+        assert_eq!(blockchain.audit_supply(), Ok(()));
+        assert!(blockchain.balances.burn("carol", 26).is_err());
+
+        // Editing the map directly bypasses the issuance tracking
+        blockchain.balances.balances.insert("mallory".to_string(), 1);
+        assert!(blockchain.audit_supply().is_err());
+    }
+
+    #[test]
+    fn test_reward_halving() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_halving_interval(2);
+        assert_eq!(blockchain.current_block_reward(), 100);
+
+        // Block 1 is still in the first period, blocks 2 and 3 in the second
+        let minted: Vec<u128> = (0..3)
+            .map(|_| blockchain.mine_pending_transactions("miner".to_string()).unwrap().transactions[0].amount)
+            .collect();
+        assert_eq!(minted, vec![100, 50, 50]);
+        assert_eq!(blockchain.current_block_reward(), 25);
+        assert_eq!(blockchain.get_balance(&"miner".to_string()), 200);
+        assert!(blockchain.is_chain_valid());
+
+        // Eventually nothing is left to halve
+        blockchain.set_halving_interval(1);
+        blockchain.set_mining_reward(u128::MAX);
+        assert_eq!(blockchain.block_reward_at(127), 1);
+        assert_eq!(blockchain.block_reward_at(128), 0);
+    }
+
+    #[test]
+    fn test_try_replace_chain() {
+        let mut node = Blockchain::new();
+        let mut fork = node.clone();
+
+        let shared = Transaction::new("alice".to_string(), "bob".to_string(), 100, 1);
+        node.add_transaction(shared.clone()).unwrap();
+        node.mine_pending_transactions("node-miner".to_string()).unwrap();
+
+        // The competing fork includes the same transfer and is one block
+        // longer, its last block holding a transfer still pending here
+        let pending = Transaction::new("alice".to_string(), "bob".to_string(), 100, 2);
+        node.add_transaction(pending.clone()).unwrap();
+        fork.add_transaction(shared).unwrap();
+        fork.mine_pending_transactions("fork-miner".to_string()).unwrap();
+        fork.add_transaction(pending).unwrap();
+        fork.mine_pending_transactions("fork-miner".to_string()).unwrap();
+
+        // Not longer than the current chain
+        assert_eq!(node.try_replace_chain(node.chain[..1].to_vec()), Ok(false));
+        assert_eq!(node.try_replace_chain(node.chain.clone()), Ok(false));
+
+        // Longer but tampered with
+        let mut tampered = fork.chain.clone();
+        tampered[2].transactions[0].amount = 1_000;
+        assert!(node.try_replace_chain(tampered).is_err());
+        assert_eq!(node.get_chain_length(), 2);
+
+        // Longer and valid: adopted with its balances replayed
+        assert_eq!(node.try_replace_chain(fork.chain.clone()), Ok(true));
+        assert_eq!(node.get_chain_length(), 3);
+        assert_eq!(node.get_balance(&"node-miner".to_string()), 0);
+        assert_eq!(node.get_balance(&"fork-miner".to_string()), 200);
+        assert_eq!(node.get_balance(&"bob".to_string()), 700);
+        assert_eq!(node.get_pending_transaction_count(), 0);
+        assert!(node.is_chain_valid());
+
+        // Mining carries on from the adopted chain
+        node.mine_pending_transactions("node-miner".to_string()).unwrap();
+        assert!(node.is_chain_valid());
+
+        // A chain from some other genesis is never a fork of this one
+        let mut stranger = Blockchain::new();
+        stranger.chain[0].timestamp += 1;
+        stranger.chain[0].hash = stranger.chain[0].calculate_hash();
+        for _ in 0..4 {
+            stranger.chain.push(stranger.chain[0].clone());
+        }
+        assert!(node.try_replace_chain(stranger.chain).is_err());
+    }
+
+    #[test]
+    fn test_rejected_replacement_keeps_pools() {
+        let mut node = Blockchain::new();
+        let mut fork = node.clone();
+        for _ in 0..2 {
+            fork.mine_pending_transactions("fork-miner".to_string()).unwrap();
+        }
+
+        node.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 1)).unwrap();
+        node.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 3)).unwrap();
+        let pending = node.pending_transactions.clone();
+        let queued = node.queued_transactions.clone();
+        assert_eq!((pending.len(), node.get_queued_transaction_count()), (1, 1));
+
+        // Replays fine, so it's only turned away by the audit
+        let mut tampered = fork.chain.clone();
+        tampered[2].merkle_root = Hash::from_string("tampered");
+        assert!(node.try_replace_chain(tampered).is_err());
+        assert_eq!(node.pending_transactions, pending);
+        assert_eq!(node.queued_transactions, queued);
+
+        // Adopting a valid fork carries the queue over
+        assert_eq!(node.try_replace_chain(fork.chain), Ok(true));
+        assert_eq!(node.queued_transactions, queued);
+        assert_eq!(node.get_pending_transaction_count(), 1);
+    }
+
+    #[test]
+    fn test_rollback_last_block() {
+        let mut blockchain = Blockchain::new();
+        assert!(blockchain.rollback_last_block().is_err());
+
+        blockchain.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 100, 1)).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let balances_before = blockchain.balances.balances.clone();
+        let issuance_before = blockchain.balances.total_issuance();
+        let state_root_before = blockchain.get_latest_block().state_root.clone();
+        let config_before = blockchain.rps_miner.config.clone();
+
+        blockchain.add_transaction(Transaction::new_with_fee("alice".to_string(), "carol".to_string(), 50, 3, 2)).unwrap();
+        blockchain.add_transaction(Transaction::new("bob".to_string(), "alice".to_string(), 20, 1)).unwrap();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let removed = blockchain.rollback_last_block().unwrap();
+        assert_eq!(removed.hash, block.hash);
+        assert_eq!(blockchain.get_chain_length(), 2);
+
+        assert_eq!(blockchain.balances.balances, balances_before);
+        assert_eq!(blockchain.balances.total_issuance(), issuance_before);
+        assert_eq!(Blockchain::compute_state_root(&blockchain.balances), state_root_before);
+        assert_eq!(blockchain.system.get_nonce(&"alice".to_string()), 1);
+        assert_eq!(blockchain.system.get_nonce(&"bob".to_string()), 0);
+        assert_eq!(blockchain.system.get_block_number(), 1);
+        assert_eq!(blockchain.rps_miner.config.blocks_mined, config_before.blocks_mined);
+        assert_eq!(blockchain.rps_miner.config.difficulty_adjustment, config_before.difficulty_adjustment);
+        assert!(blockchain.is_chain_valid());
+
+        // And the chain can be extended again from there
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_tampered_balances_rejected_on_load() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 100, 1)).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(blockchain.recompute_balances().balances, blockchain.balances.balances);
+
+        let path = std::env::temp_dir().join(format!("phlopchain_tampered_{}.json", std::process::id()));
+        let mut json: serde_json::Value = serde_json::to_value(&blockchain).unwrap();
+        json["balances"]["balances"]["alice"] = serde_json::json!(1_000_000);
+        fs::write(&path, json.to_string()).unwrap();
+
+        let error = Blockchain::load_from_file(&path).unwrap_err();
+        assert!(error.contains("don't match the transaction history"), "{}", error);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_full_state_round_trips_through_json() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_transaction(Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 100, 2, 1)).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.add_transaction(Transaction::new("bob".to_string(), "carol".to_string(), 5, 1)).unwrap();
+
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let restored: Blockchain = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.balances.balances, blockchain.balances.balances);
+        assert_eq!(restored.balances.total_issuance(), blockchain.balances.total_issuance());
+        assert_eq!(restored.system.get_nonce(&"alice".to_string()), 1);
+        assert_eq!(restored.pending_transactions, blockchain.pending_transactions);
+        let hashes = |chain: &Blockchain| chain.chain.iter().map(|block| block.hash.clone()).collect::<Vec<_>>();
+        assert_eq!(hashes(&restored), hashes(&blockchain));
+        assert!(restored.is_chain_valid());
+    }
+
+    #[test]
+    fn test_get_nonce() {
+        let mut blockchain = Blockchain::new();
+        let alice = "alice".to_string();
+        assert_eq!(blockchain.get_nonce(&alice), 0);
+        assert_eq!(blockchain.get_next_nonce(&alice), 1);
+
+        blockchain.add_transaction(Transaction::new(alice.clone(), "bob".to_string(), 10, 1)).unwrap();
+        assert_eq!(blockchain.get_nonce(&alice), 0);
+        assert_eq!(blockchain.get_next_nonce(&alice), 2);
+
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(blockchain.get_nonce(&alice), 1);
+        assert_eq!(blockchain.get_next_nonce(&alice), 2);
+    }
+
+    #[test]
+    fn test_mempool_evicts_lowest_fee() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_max_mempool_size(3);
+        for sender in ["carol", "dave", "erin"] {
+            blockchain.balances.set_balance(sender, 100).unwrap();
+        }
+        let tx = |from: &str, fee: u128| Transaction::new_with_fee(from.to_string(), "bob".to_string(), 10, fee, 1);
+
+        blockchain.add_transaction(tx("alice", 2)).unwrap();
+        blockchain.add_transaction(tx("carol", 1)).unwrap();
+        assert!(!blockchain.mempool_is_full());
+        blockchain.add_transaction(tx("dave", 1)).unwrap();
+        assert!(blockchain.mempool_is_full());
+
+        // Not above the lowest fee present, so there's nothing to replace
+        assert_eq!(blockchain.add_transaction(tx("erin", 1)), Err(BlockchainError::MempoolFull));
+
+        // The most recent of the lowest-fee transactions goes first
+        blockchain.add_transaction(tx("erin", 5)).unwrap();
+        let senders = |chain: &Blockchain| chain.pending_transactions.iter().map(|tx| tx.from.clone()).collect::<Vec<_>>();
+        assert_eq!(senders(&blockchain), vec!["alice", "carol", "erin"]);
+
+        blockchain.add_transaction(tx("dave", 3)).unwrap();
+        assert_eq!(senders(&blockchain), vec!["alice", "erin", "dave"]);
+        assert_eq!(blockchain.get_pending_transaction_count(), 3);
+    }
+
+    #[test]
+    fn test_evicted_nonce_can_be_resubmitted() {
+        let allocations = ["alice", "bob", "carol", "dave"].iter().map(|name| (name.to_string(), 1_000)).collect();
+        let mut blockchain = Blockchain::new_with_genesis(allocations, RPSMiningConfig::new());
+        blockchain.set_max_mempool_size(4);
+        let tx = |from: &str, fee: u128, nonce: u32| Transaction::new_with_fee(from.to_string(), "erin".to_string(), 10, fee, nonce);
+
+        blockchain.add_transaction(tx("alice", 1, 1)).unwrap();
+        blockchain.add_transaction(tx("alice", 5, 2)).unwrap();
+        blockchain.add_transaction(tx("carol", 5, 1)).unwrap();
+        blockchain.add_transaction(tx("dave", 5, 1)).unwrap();
+
+        // Evicting alice's nonce 1 strands her nonce 2, which goes back to
+        // the queue rather than sitting in the mempool unminable
+        blockchain.add_transaction(tx("bob", 3, 1)).unwrap();
+        assert_eq!(blockchain.get_pending_transaction_count(), 3);
+        assert_eq!(blockchain.get_queued_transaction_count(), 1);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 1);
+
+        // Resubmitting nonce 1 brings nonce 2 back with it, which in turn
+        // evicts bob's lower fee
+        blockchain.add_transaction(tx("alice", 4, 1)).unwrap();
+        assert_eq!(blockchain.get_queued_transaction_count(), 0);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 3);
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 5);
+        assert_eq!(blockchain.get_nonce(&"alice".to_string()), 2);
+        assert_eq!(blockchain.get_nonce(&"bob".to_string()), 0);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_sender_continues_after_expiry() {
+        let mut blockchain = Blockchain::new();
+        let now = current_timestamp();
+        let transfer = |nonce: u32| Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce);
+
+        blockchain.add_transaction(transfer(1).with_valid_until(now + 3600)).unwrap();
+        blockchain.add_transaction(transfer(2)).unwrap();
+        // Nonce 1 runs out while waiting
+        blockchain.pending_transactions[0] = transfer(1).with_valid_until(now - 10);
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+        assert_eq!(blockchain.get_queued_transaction_count(), 1);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 1);
+
+        // Sending nonce 1 again lets nonce 2 through behind it
+        blockchain.add_transaction(transfer(1)).unwrap();
+        assert_eq!(blockchain.get_pending_transaction_count(), 2);
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(blockchain.get_nonce(&"alice".to_string()), 2);
+    }
+
+    #[test]
+    fn test_expired_transaction_purged() {
+        let mut blockchain = Blockchain::new();
+        let now = current_timestamp();
+
+        let expired = Transaction::new("alice".to_string(), "bob".to_string(), 10, 1).with_valid_until(now - 10);
+        assert!(!expired.is_valid());
+        assert_eq!(blockchain.add_transaction(expired.clone()), Err(BlockchainError::TransactionExpired));
+
+        // One that expires while waiting in the mempool is dropped at mining
+        blockchain.pending_transactions.push_back(expired);
+        let live = Transaction::new("bob".to_string(), "carol".to_string(), 10, 1).with_valid_until(now + 3600);
+        blockchain.add_transaction(live.clone()).unwrap();
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.transactions[1], live);
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+        assert_eq!(blockchain.get_balance(&"alice".to_string()), 1000);
+        assert!(blockchain.is_chain_valid());
+
+        // The expiry is covered by the hash
+        let mut extended = live.clone();
+        extended.valid_until = Some(now + 7200);
+        assert!(!extended.is_valid());
+    }
+
+    #[test]
+    fn test_nonce_gap_is_queued() {
+        let mut blockchain = Blockchain::new();
+        let first = Transaction::new("alice".to_string(), "bob".to_string(), 10, 1);
+        blockchain.add_transaction(first).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        // Nonce 3 arrives before 2, so it waits outside the mempool
+        let third = Transaction::new("alice".to_string(), "bob".to_string(), 30, 3);
+        blockchain.add_transaction(third.clone()).unwrap();
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+        assert_eq!(blockchain.get_queued_transaction_count(), 1);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 2);
+
+        // A block mined now has nothing of alice's to run
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 1);
+
+        // Nonce 2 fills the gap and promotes nonce 3
+        let second = Transaction::new("alice".to_string(), "bob".to_string(), 20, 2);
+        blockchain.add_transaction(second.clone()).unwrap();
+        assert_eq!(blockchain.get_pending_transaction_count(), 2);
+        assert_eq!(blockchain.get_queued_transaction_count(), 0);
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions[1..], [second, third]);
+        assert_eq!(blockchain.get_nonce(&"alice".to_string()), 3);
+        assert_eq!(blockchain.get_balance(&"bob".to_string()), 560);
+
+        // Nonces already used are still rejected outright
+        let replay = Transaction::new("alice".to_string(), "bob".to_string(), 10, 2);
+        assert_eq!(blockchain.add_transaction(replay), Err(BlockchainError::InvalidNonce { expected: 4, got: 2 }));
+    }
+
+    #[test]
+    fn test_only_runnable_transactions_are_mined() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_max_transactions_per_block(2);
+        blockchain.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 1)).unwrap();
+        blockchain
+            .add_transaction(Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 10, 50, 2))
+            .unwrap();
+
+        // The higher fee can't jump ahead of the nonce before it
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions[1].nonce, 1);
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions[1].nonce, 2);
+        assert!(blockchain.audit_supply().is_ok());
+    }
+
+    #[test]
+    fn test_new_with_genesis() {
+        let allocations: BTreeMap<String, u128> =
+            [("carol".to_string(), 5000), ("dave".to_string(), 70)].into_iter().collect();
+        let blockchain = Blockchain::new_with_genesis(allocations.clone(), RPSMiningConfig::with_players(10));
+        assert_eq!(blockchain.get_balance(&"carol".to_string()), 5000);
+        assert_eq!(blockchain.get_balance(&"alice".to_string()), 0);
+        assert_eq!(blockchain.rps_miner.config.total_players, 10);
+        assert_ne!(blockchain.chain[0].hash, Blockchain::new().chain[0].hash);
+
+        let mut other_allocations = allocations;
+        other_allocations.insert("dave".to_string(), 71);
+        let other = Blockchain::new_with_genesis(other_allocations, RPSMiningConfig::with_players(10));
+        assert_ne!(blockchain.chain[0].state_root, other.chain[0].state_root);
+        assert_ne!(blockchain.chain[0].hash, other.chain[0].hash);
+
+        // Replays start from the chosen allocations
+        let mut blockchain = blockchain;
+        blockchain.add_transaction(Transaction::new("carol".to_string(), "dave".to_string(), 30, 1)).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(blockchain.is_chain_valid());
+        assert_eq!(blockchain.recompute_balances().get_balance(&"dave".to_string()), 100);
+    }
+
+    #[test]
+    fn test_block_events() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 25, 1)).unwrap();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        let games = block.rps_mining_result.as_ref().unwrap().total_games;
+
+        assert_eq!(
+            blockchain.get_block_events(1),
+            [
+                Event::Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: 25 },
+                Event::Reward { to: "miner".to_string(), amount: blockchain.mining_reward },
+                Event::BlockMined { index: 1, games },
+            ]
+        );
+        assert!(blockchain.get_block_events(0).is_empty());
+        assert!(blockchain.get_block_events(2).is_empty());
+
+        // Replaying the chain deposits the same events
+        let (_, system) = blockchain.replay_state(&blockchain.chain).unwrap();
+        assert_eq!(system.get_events(1), blockchain.get_block_events(1));
+
+        // Rolling the block back drops its events
+        blockchain.rollback_last_block().unwrap();
+        assert!(blockchain.get_block_events(1).is_empty());
+    }
+
+    #[test]
+    fn test_skipped_transactions_are_events() {
+        let mut blockchain = Blockchain::new();
+        // Admission would refuse the overdraft, so it's slipped in directly,
+        // as if alice's balance had dropped since it was accepted
+        blockchain.pending_transactions.push_back(Transaction::new("alice".to_string(), "bob".to_string(), 5_000, 1));
+        blockchain.pending_transactions.push_back(Transaction::new("alice".to_string(), "bob".to_string(), 10, 2));
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        let skipped: Vec<&Event> = blockchain
+            .get_block_events(1)
+            .iter()
+            .filter(|event| matches!(event, Event::TransactionSkipped { .. }))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                &Event::TransactionSkipped {
+                    from: "alice".to_string(),
+                    nonce: 1,
+                    reason: BlockchainError::InsufficientBalance { have: 1000, need: 5000 }.to_string(),
+                },
+                &Event::TransactionSkipped {
+                    from: "alice".to_string(),
+                    nonce: 2,
+                    reason: "Nonce no longer follows on from the sender's last".to_string(),
+                },
+            ]
+        );
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_blocks_mined_by() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner1".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner2".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner1".to_string()).unwrap();
+
+        assert_eq!(blockchain.blocks_mined_by(&"miner1".to_string()), 2);
+        assert_eq!(blockchain.blocks_mined_by(&"miner2".to_string()), 1);
+        assert_eq!(blockchain.blocks_mined_by(&"alice".to_string()), 0);
+
+        // Replays and rollbacks keep the counts in step with the chain
+        let (_, system) = blockchain.replay_state(&blockchain.chain).unwrap();
+        assert_eq!(system.blocks_mined_by(&"miner1".to_string()), 2);
+        blockchain.rollback_last_block().unwrap();
+        assert_eq!(blockchain.blocks_mined_by(&"miner1".to_string()), 1);
+    }
+
+    #[test]
+    fn test_miner_leaderboard() {
+        let mut blockchain = Blockchain::new();
+        assert!(blockchain.get_miner_leaderboard().is_empty());
+
+        blockchain.mine_pending_transactions("miner1".to_string()).unwrap();
+        blockchain
+            .add_transaction(Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 10, 7, 1))
+            .unwrap();
+        blockchain.mine_pending_transactions("miner2".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner2".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner0".to_string()).unwrap();
+
+        let reward = blockchain.mining_reward;
+        assert_eq!(
+            blockchain.get_miner_leaderboard(),
+            vec![
+                ("miner2".to_string(), 2 * reward + 7),
+                ("miner0".to_string(), reward),
+                ("miner1".to_string(), reward),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_headers() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let headers = blockchain.get_headers();
+        assert_eq!(headers.len(), blockchain.get_chain_length());
+        for (header, block) in headers.iter().zip(&blockchain.chain) {
+            assert_eq!(header.hash, block.hash);
+        }
+        assert!(headers.windows(2).all(|pair| pair[1].links_to(&pair[0])));
+    }
+
+    #[test]
+    fn test_blockchain_error_messages() {
+        // The messages are the ones clients saw back when errors were strings
+        assert_eq!(BlockchainError::InsufficientBalance { have: 1, need: 2 }.to_string(), "Insufficient balance");
+        assert_eq!(BlockchainError::InvalidNonce { expected: 2, got: 1 }.to_string(), "Invalid nonce");
+        assert_eq!(BlockchainError::Overflow("Amount plus fee").to_string(), "Amount plus fee overflows");
+
+        let timeout = MiningError::RoundLimitExceeded { rounds: 10, total_games: 500 };
+        let error = BlockchainError::from(timeout.clone());
+        assert!(error.to_string().starts_with("RPS Mining failed: Mining timeout"));
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), timeout.to_string());
+    }
+
+    #[test]
+    fn test_add_transactions_batch() {
+        let mut blockchain = Blockchain::new();
+
+        // Three chained nonces spending 900 of alice's 1000 between them
+        let batch: Vec<Transaction> = (1..=3)
+            .map(|nonce| Transaction::new("alice".to_string(), "bob".to_string(), 300, nonce))
+            .collect();
+        let results = blockchain.add_transactions(batch);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(blockchain.get_pending_transaction_count(), 3);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 4);
+
+        // Results come back in order, judged against what went before
+        let results = blockchain.add_transactions(vec![
+            Transaction::new("alice".to_string(), "bob".to_string(), 100, 4),
+            Transaction::new("alice".to_string(), "bob".to_string(), 1, 5),
+            Transaction::new("bob".to_string(), "alice".to_string(), 50, 1),
+        ]);
+        assert_eq!(results[0], Ok(()));
+        assert_eq!(results[1], Err(BlockchainError::InsufficientBalance { have: 0, need: 1 }));
+        assert_eq!(results[2], Ok(()));
+
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 6);
+        assert_eq!(blockchain.get_balance(&"alice".to_string()), 50);
+    }
+
+    #[test]
+    fn test_prune_keeps_headers() {
+        let mut blockchain = Blockchain::new();
+        for nonce in 1..=4 {
+            blockchain
+                .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce))
+                .unwrap();
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let headers = blockchain.get_headers();
+        let balances = blockchain.balances.balances.clone();
+        let old_tx = blockchain.chain[1].transactions[1].hash.clone();
+        let recent_tx = blockchain.chain[3].transactions[1].hash.clone();
+        assert!(blockchain.get_transaction_proof(&old_tx).is_some());
+
+        blockchain.prune(2);
+        assert_eq!(blockchain.get_chain_length(), 5);
+        assert_eq!(blockchain.get_headers(), headers);
+        assert!(blockchain.is_chain_valid(), "{:?}", blockchain.audit());
+        assert_eq!(blockchain.recompute_balances().balances, balances);
+
+        // Blocks 1 and 2 lost their bodies, the last two kept theirs
+        let pruned: Vec<bool> = blockchain.chain.iter().map(Block::is_pruned).collect();
+        assert_eq!(pruned, [false, true, true, false, false]);
+        assert!(blockchain.get_transaction_proof(&old_tx).is_none());
+        assert!(blockchain.get_transaction_proof(&recent_tx).is_some());
+
+        // The chain carries on from the pruned state
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 5))
+            .unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.rollback_last_block().unwrap();
+        blockchain.prune(0);
+        assert!(blockchain.is_chain_valid(), "{:?}", blockchain.audit());
+        assert!(blockchain.rollback_last_block().is_err());
+    }
+
+    #[test]
+    fn test_pruned_chain_still_detects_tampering() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..3 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        blockchain.prune(1);
+
+        // Without the pruned state there's nothing to check block 2 against
+        let mut stateless = blockchain.clone();
+        stateless.pruned_state = None;
+        assert!(!stateless.is_chain_valid());
+
+        // Nor can the pruned state disagree with the header it stands in for
+        let mut forged = blockchain.clone();
+        forged.pruned_state.as_mut().unwrap().balances.set_balance("mallory", 1_000).unwrap();
+        assert!(forged.audit().iter().any(|finding| finding.check == AuditCheck::StateRoot));
+    }
+
+    #[test]
+    fn test_export_json_is_stable() {
+        let mut blockchain = Blockchain::new();
+        for (to, nonce) in [("zoe", 1), ("carol", 2), ("mallory", 3)] {
+            blockchain
+                .add_transaction(Transaction::new("alice".to_string(), to.to_string(), 10, nonce))
+                .unwrap();
+        }
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let exported = blockchain.export_json();
+        assert_eq!(exported, blockchain.clone().export_json());
+
+        // A round trip comes back byte-identical
+        let imported = Blockchain::import_json(&exported).unwrap();
+        assert_eq!(imported.export_json(), exported);
+
+        // Accounts come out sorted, whatever order they were credited in
+        let carol = exported.find("\"carol\": 10").unwrap();
+        let zoe = exported.find("\"zoe\": 10").unwrap();
+        assert!(carol < zoe);
+    }
+
+    #[test]
+    fn test_import_json_validates() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        assert!(matches!(Blockchain::import_json("{"), Err(BlockchainError::InvalidJson(_))));
+
+        let mut tampered = blockchain.clone();
+        tampered.balances.set_balance("mallory", 1_000).unwrap();
+        assert_eq!(Blockchain::import_json(&tampered.export_json()).unwrap_err(), BlockchainError::BalanceMismatch);
+
+        let mut tampered = blockchain.clone();
+        tampered.chain[1].timestamp += 1;
+        assert!(matches!(
+            Blockchain::import_json(&tampered.export_json()),
+            Err(BlockchainError::InvalidChain { block_index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_transaction_index() {
+        let mut blockchain = Blockchain::new();
+        let mut mined = Vec::new();
+        for nonce in 1..=3 {
+            let tx = Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce);
+            blockchain.add_transaction(tx.clone()).unwrap();
+            assert!(!blockchain.contains_transaction(&tx.hash));
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+            mined.push(tx);
+        }
+
+        for (i, tx) in mined.iter().enumerate() {
+            assert!(blockchain.contains_transaction(&tx.hash));
+            let (block, found, position) = blockchain.find_transaction(&tx.hash).unwrap();
+            assert_eq!((block.index, found, position), (i as u32 + 1, tx, 1));
+        }
+        assert!(!blockchain.contains_transaction(&Hash::from_string("unknown")));
+
+        // Rolling back unindexes the block, reloading rebuilds the index
+        blockchain.rollback_last_block().unwrap();
+        assert!(!blockchain.contains_transaction(&mined[2].hash));
+        let reloaded = Blockchain::import_json(&blockchain.export_json()).unwrap();
+        assert!(reloaded.contains_transaction(&mined[1].hash));
+        assert_eq!(reloaded.get_transaction_proof(&mined[1].hash).unwrap().2, 2);
+
+        // Pruned transactions are gone from the index too
+        blockchain.prune(1);
+        assert!(!blockchain.contains_transaction(&mined[0].hash));
+        assert!(blockchain.contains_transaction(&mined[1].hash));
+
+        // A block pushed onto the chain directly is still found by scanning
+        let mut unindexed = blockchain.get_latest_block().clone();
+        unindexed.index += 1;
+        let tx = Transaction::new("carol".to_string(), "dave".to_string(), 1, 1);
+        unindexed.transactions.push(tx.clone());
+        blockchain.chain.push(unindexed);
+        let (block, found, position) = blockchain.find_transaction(&tx.hash).unwrap();
+        assert_eq!((block.index, found, position), (3, &tx, 2));
+    }
+
+    #[test]
+    fn test_coinbase_kind() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 10, 3, 1))
+            .unwrap();
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert!(block.transactions[0].is_coinbase());
+        assert!(!block.transactions[1].is_coinbase());
+        assert_eq!(Blockchain::get_total_fees_in_block(&block), 3);
+
+        // Only mining makes coinbases, even with the network account unreserved
+        blockchain.set_reserved_senders(BTreeSet::new());
+        let coinbase = Transaction::coinbase("mallory".to_string(), 1_000);
+        assert_eq!(blockchain.add_transaction(coinbase), Err(BlockchainError::InvalidTransaction));
+
+        // A block whose reward is dressed up as a transfer no longer validates
+        let mut forged = blockchain.clone();
+        let reward = &mut forged.chain[1].transactions[0];
+        reward.kind = crate::transaction::TransactionKind::Transfer;
+        reward.hash = reward.calculate_hash();
+        assert!(forged.audit().iter().any(|finding| finding.check == AuditCheck::Transaction));
+    }
+
+    #[test]
+    fn test_get_all_balances() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "carol".to_string(), 25, 1))
+            .unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        let balances = blockchain.get_all_balances();
+        assert_eq!(balances["carol"], 25);
+        assert_eq!(balances["miner"], 100);
+
+        // Rebuilding the state tree from the snapshot gives the same root
+        let mut tree = FastMerkleTree::new();
+        for (account, balance) in &balances {
+            tree.add_leaf(Hash::from_string(&format!("{}:{}", account, balance)));
+        }
+        tree.build();
+        assert_eq!(tree.get_root(), blockchain.create_state_merkle_tree().get_root());
+        assert_eq!(tree.get_root(), Some(&blockchain.get_latest_block().state_root));
+    }
+
+    #[test]
+    fn test_set_mining_reward() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(blockchain.get_mining_reward(), 100);
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        blockchain.set_mining_reward(250);
+        assert_eq!(blockchain.get_mining_reward(), 250);
+        assert_eq!(blockchain.current_block_reward(), 250);
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions[0].amount, 250);
+        assert_eq!(blockchain.get_balance(&"miner".to_string()), 350);
+
+        // Changing it again, even before the next block, leaves mined blocks valid
+        blockchain.set_mining_reward(0);
+        blockchain.set_mining_reward(40);
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(blockchain.get_balance(&"miner".to_string()), 390);
+        assert_eq!(blockchain.block_reward_at(1), 100);
+        assert!(blockchain.is_chain_valid(), "{:?}", blockchain.audit());
+
+        let reloaded = Blockchain::import_json(&blockchain.export_json()).unwrap();
+        assert_eq!(reloaded.block_reward_at(2), 250);
+    }
+
+    #[test]
+    fn test_block_iterators() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..3 {
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let indices = |blocks: Vec<&Block>| blocks.iter().map(|block| block.index).collect::<Vec<_>>();
+
+        assert_eq!(indices(blockchain.blocks().collect()), vec![0, 1, 2, 3]);
+        assert_eq!(indices(blockchain.non_genesis_blocks().collect()), vec![1, 2, 3]);
+        assert_eq!(indices(blockchain.blocks_in_range(1, 3).collect()), vec![1, 2]);
+        assert_eq!(indices(blockchain.blocks_in_range(2, 100).collect()), vec![2, 3]);
+        assert_eq!(blockchain.blocks_in_range(3, 1).count(), 0);
+        assert_eq!(blockchain.blocks_in_range(10, 20).count(), 0);
+
+        // Genesis alone has no mined blocks
+        assert_eq!(Blockchain::new().non_genesis_blocks().count(), 0);
+    }
+
+    #[test]
+    fn test_difficulty_history() {
+        let mut blockchain = Blockchain::new();
+        assert!(blockchain.get_difficulty_history().is_empty());
+
+        let mut expected = Vec::new();
+        for _ in 0..4 {
+            expected.push(blockchain.get_rps_difficulty_info().difficulty_score());
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+            assert_eq!(blockchain.get_difficulty_history().len(), blockchain.get_chain_length() - 1);
+        }
+        let history = blockchain.get_difficulty_history().to_vec();
+        assert_eq!(history.iter().map(|&(index, _)| index).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(history.iter().map(|&(_, score)| score).collect::<Vec<_>>(), expected);
+        // The difficulty ratchets up as blocks are mined
+        assert!(history[3].1 > history[0].1);
+
+        // A failed mine leaves no entry behind
+        blockchain.rps_miner.config.max_rounds = 0;
+        assert!(blockchain.mine_pending_transactions("miner".to_string()).is_err());
+        assert_eq!(blockchain.get_difficulty_history(), history.as_slice());
+        blockchain.rps_miner.config.max_rounds = RPSMiningConfig::new().max_rounds;
+
+        // A loaded chain replays the same history, and rollback drops the last entry
+        let mut loaded = Blockchain::import_json(&blockchain.export_json()).unwrap();
+        assert_eq!(loaded.get_difficulty_history(), history.as_slice());
+        loaded.rollback_last_block().unwrap();
+        assert_eq!(loaded.get_difficulty_history(), &history[..3]);
+    }
+
+    #[test]
+    fn test_prune_twice() {
+        let mut blockchain = Blockchain::new();
+        for nonce in 1..=5 {
+            blockchain
+                .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce))
+                .unwrap();
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let balances = blockchain.balances.balances.clone();
+
+        blockchain.prune(1);
+        assert_eq!(blockchain.pruned_state.as_ref().unwrap().through, 4);
+
+        // Keeping more than is left can't bring bodies back, so nothing changes
+        blockchain.prune(3);
+        assert_eq!(blockchain.pruned_state.as_ref().unwrap().through, 4);
+        assert!(blockchain.audit().is_empty(), "{:?}", blockchain.audit());
+        assert_eq!(blockchain.recompute_balances().balances, balances);
+
+        // Pruning further still works from the earlier pruned state
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.prune(1);
+        assert_eq!(blockchain.pruned_state.as_ref().unwrap().through, 5);
+        assert!(blockchain.audit().is_empty(), "{:?}", blockchain.audit());
+    }
+
+    #[test]
+    fn test_import_checks_total_issuance() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&blockchain.export_json()).unwrap();
+
+        // Dropped, the issuance defaults to 0, which can't cover the balances
+        json["balances"].as_object_mut().unwrap().remove("total_issuance");
+        assert!(matches!(
+            Blockchain::import_json(&json.to_string()),
+            Err(BlockchainError::SupplyMismatch(_))
+        ));
+
+        json["balances"]["total_issuance"] = serde_json::json!(1);
+        assert!(matches!(
+            Blockchain::import_json(&json.to_string()),
+            Err(BlockchainError::SupplyMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_mine_keeps_transactions() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 1))
+            .unwrap();
+
+        // Mining gives up before finding a winner, so the transfer must stay pending
+        blockchain.rps_miner.config.max_total_games = 1;
+        assert!(blockchain.mine_pending_transactions("miner".to_string()).is_err());
+        assert_eq!(blockchain.get_pending_transaction_count(), 1);
+        assert_eq!(blockchain.get_next_nonce(&"alice".to_string()), 2);
+
+        // And it's mined once mining can succeed again
+        blockchain.rps_miner.config.max_total_games = RPSMiningConfig::new().max_total_games;
+        let block = blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(blockchain.get_pending_transaction_count(), 0);
+    }
 }
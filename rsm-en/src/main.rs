@@ -8,12 +8,131 @@ mod transaction;
 mod blockchain;
 mod rps_mining;
 
+const USAGE: &str = "Usage:
+  blockchain [demo]                            Run the scripted demonstration
+  blockchain serve                             Start the web interface
+  blockchain mine [--blocks N] [--miner NAME]  Mine N blocks headless and print a summary";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Demo,
+    Serve,
+    Mine { blocks: u32, miner: String },
+}
+
+/// Maps the arguments after the program name to a `Command`. No arguments
+/// runs the demo, as the binary always did before it took subcommands.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+
+    let command = match args.next().as_deref() {
+        None | Some("demo") => Command::Demo,
+        Some("serve") => Command::Serve,
+        Some("mine") => {
+            let mut blocks = 1;
+            let mut miner = "miner".to_string();
+
+            while let Some(flag) = args.next() {
+                let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+                match flag.as_str() {
+                    "--blocks" => {
+                        blocks = value.parse().map_err(|_| format!("Invalid block count: {}", value))?;
+                    }
+                    "--miner" => miner = value,
+                    _ => return Err(format!("Unknown option for mine: {}", flag)),
+                }
+            }
+            Command::Mine { blocks, miner }
+        }
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+    };
+
+    match args.next() {
+        Some(extra) => Err(format!("Unexpected argument: {}", extra)),
+        None => Ok(command),
+    }
+}
+
 fn main() {
     println!("PhlopChain - Fast Merkle Tree Blockchain Implementation");
     println!("{}", "=".repeat(60));
 
-    // Run CLI demonstration
-    run_cli_demo();
+    match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Demo) => run_cli_demo(),
+        Ok(Command::Serve) => run_server(),
+        Ok(Command::Mine { blocks, miner }) => run_headless_mining(blocks, &miner),
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// The web interface is its own binary; start the one built alongside this one
+#[cfg(feature = "web")]
+fn run_server() {
+    let server = match std::env::current_exe() {
+        Ok(exe) => exe.with_file_name(format!("web_server{}", std::env::consts::EXE_SUFFIX)),
+        Err(e) => {
+            eprintln!("Could not locate the web server: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match std::process::Command::new(&server).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            eprintln!("Web server exited with {}", status);
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        Err(e) => {
+            eprintln!("Failed to start {}: {}", server.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "web"))]
+fn run_server() {
+    eprintln!("This build has no web interface; rebuild with the `web` feature to serve");
+    std::process::exit(1);
+}
+
+fn run_headless_mining(blocks: u32, miner: &str) {
+    let mut blockchain = Blockchain::new();
+    println!("Mining {} blocks as {}...", blocks, miner);
+
+    for _ in 0..blocks {
+        match blockchain.mine_pending_transactions(miner.to_string()) {
+            Ok(block) => {
+                if let Some(ref rps_result) = block.rps_mining_result {
+                    println!(
+                        "Block {}: {} rounds, {} games, {} ms",
+                        block.index, rps_result.rounds, rps_result.total_games, rps_result.mining_time_ms
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Mining failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    let mined = blockchain.get_chain_length() - 1;
+    let total_ms: u128 = blockchain
+        .non_genesis_blocks()
+        .filter_map(|block| block.rps_mining_result.as_ref())
+        .map(|result| result.mining_time_ms)
+        .sum();
+
+    println!("\nMining Summary:");
+    println!("Blocks mined: {}", mined);
+    println!("Total RPS games played: {}", blockchain.get_total_rps_games());
+    println!("Total mining time: {} ms", total_ms);
+    println!("Final difficulty score: {:.2}", blockchain.get_rps_difficulty_info().difficulty_score());
+    println!("{} balance: {} tokens", miner, blockchain.get_balance(&miner.to_string()));
+    println!("Blockchain valid: {}", blockchain.is_chain_valid());
 }
 
 fn run_cli_demo() {
@@ -86,7 +205,7 @@ fn run_cli_demo() {
                 println!("  - Rounds played: {}", rps_result.rounds);
                 println!("  - Total games: {}", rps_result.total_games);
                 println!("  - Mining time: {} ms", rps_result.mining_time_ms);
-                println!("  - Players who achieved required wins: {}", rps_result.winning_players.len());
+                println!("  - Players who achieved required wins: {}", rps_result.winning_player_count);
                 
                 // Show difficulty progression
                 let difficulty_info = blockchain.get_rps_difficulty_info();
@@ -116,7 +235,7 @@ fn run_cli_demo() {
     println!("\nBlockchain Statistics:");
     println!("Chain length: {} blocks", blockchain.get_chain_length());
     println!("Current RPS difficulty score: {:.2}", blockchain.get_rps_difficulty_info().difficulty_score());
-    println!("Mining reward: {} tokens", blockchain.mining_reward);
+    println!("Mining reward: {} tokens", blockchain.current_block_reward());
     println!("Network game rate: {:.2} games/s", blockchain.get_network_hash_rate());
     println!("Total RPS games played: {}", blockchain.get_total_rps_games());
 
@@ -142,7 +261,7 @@ fn run_cli_demo() {
     let alice_history = blockchain.get_transaction_history(&"alice".to_string());
     for (i, tx) in alice_history.iter().enumerate() {
         println!("{}. {} -> {} ({} tokens) [{}]", 
-                 i + 1, tx.from, tx.to, tx.amount, tx.hash.to_hex()[..8].to_string());
+                 i + 1, tx.from, tx.to, tx.amount, &tx.hash.to_hex()[..8]);
     }
 
     // Display state root
@@ -200,12 +319,12 @@ fn run_cli_demo() {
     println!("Blockchain valid: {}", blockchain.is_chain_valid());
     
     // Display all blocks with RPS information
-    for (i, block) in blockchain.chain.iter().enumerate() {
-        println!("\nBlock {}: {}", i, block.hash.to_hex()[..16].to_string());
+    for (i, block) in blockchain.blocks().enumerate() {
+        println!("\nBlock {}: {}", i, &block.hash.to_hex()[..16]);
         println!("  Transactions: {}", block.transactions.len());
         println!("  Timestamp: {}", block.timestamp);
         if i > 0 {
-            println!("  Previous: {}", block.previous_hash.to_hex()[..16].to_string());
+            println!("  Previous: {}", &block.previous_hash.to_hex()[..16]);
             if let Some(ref rps_result) = block.rps_mining_result {
                 println!("  RPS: {} rounds, {} games, {} ms", 
                          rps_result.rounds, rps_result.total_games, rps_result.mining_time_ms);
@@ -217,3 +336,36 @@ fn run_cli_demo() {
 
     println!("\n🎉 PhlopChain RPS Mining demonstration completed successfully!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]), Ok(Command::Demo));
+        assert_eq!(parse(&["demo"]), Ok(Command::Demo));
+        assert_eq!(parse(&["serve"]), Ok(Command::Serve));
+        assert_eq!(
+            parse(&["mine"]),
+            Ok(Command::Mine { blocks: 1, miner: "miner".to_string() })
+        );
+        assert_eq!(
+            parse(&["mine", "--blocks", "5", "--miner", "carol"]),
+            Ok(Command::Mine { blocks: 5, miner: "carol".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert_eq!(parse(&["launch"]), Err("Unknown command: launch".to_string()));
+        assert!(parse(&["mine", "--blocks"]).is_err());
+        assert!(parse(&["mine", "--blocks", "many"]).is_err());
+        assert!(parse(&["mine", "--speed", "fast"]).is_err());
+        assert!(parse(&["serve", "now"]).is_err());
+    }
+}
@@ -1,8 +1,47 @@
 use sha2::{Digest, Sha256};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+// Domain separation prefixes so a leaf can never be reinterpreted as an
+// internal node (and vice versa)
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+// Stands in for the missing right sibling of an odd node out
+const EMPTY_SIBLING: Hash = Hash::zero();
+
+/// Digest function behind `Hash` values and Merkle trees. Any function with
+/// a 32-byte output fits in a `Hash`.
+pub trait Hasher {
+    /// Digest of `parts` fed in one after the other
+    fn digest(parts: &[&[u8]]) -> [u8; 32];
+}
+
+fn digest_parts<D: Digest>(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.finalize());
+    hash
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// SHA-256, used throughout the chain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        digest_parts::<Sha256>(parts)
+    }
+}
+
+/// Ordered lexicographically over its bytes, i.e. the same as its hex form
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Hash([u8; 32]);
 
 impl Hash {
@@ -11,27 +50,48 @@ impl Hash {
         Self(data)
     }
 
+    /// All-zero hash, the explicit "no value" marker for empty Merkle roots
+    /// and the genesis block's previous hash
+    pub const fn zero() -> Self {
+        Self([0u8; 32])
+    }
+
+    #[allow(dead_code)]
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0u8; 32]
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Self {
-        let mut hasher = Sha256::new();
-        hasher.update(bytes);
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        Self(hash)
+        Self::from_bytes_with::<Sha256Hasher>(bytes)
+    }
+
+    /// Digest of `bytes` under hash function `H`
+    pub fn from_bytes_with<H: Hasher>(bytes: &[u8]) -> Self {
+        Self(H::digest(&[bytes]))
     }
 
     pub fn from_string(data: &str) -> Self {
         Self::from_bytes(data.as_bytes())
     }
 
+    /// Hash of a tree leaf, prefixed so it can't collide with an internal node
+    #[allow(dead_code)]
+    pub fn leaf_hash(&self) -> Hash {
+        self.leaf_hash_with::<Sha256Hasher>()
+    }
+
+    pub fn leaf_hash_with<H: Hasher>(&self) -> Hash {
+        Hash(H::digest(&[&[LEAF_PREFIX], &self.0]))
+    }
+
+    /// Hash of an internal node from its two children
+    #[allow(dead_code)]
     pub fn combine(&self, other: &Hash) -> Hash {
-        let mut hasher = Sha256::new();
-        hasher.update(&self.0);
-        hasher.update(&other.0);
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        Hash(hash)
+        self.combine_with::<Sha256Hasher>(other)
+    }
+
+    pub fn combine_with<H: Hasher>(&self, other: &Hash) -> Hash {
+        Hash(H::digest(&[&[NODE_PREFIX], &self.0, &other.0]))
     }
 
     #[allow(dead_code)]
@@ -42,28 +102,98 @@ impl Hash {
     pub fn to_hex(&self) -> String {
         hex::encode(self.0)
     }
+
+    /// Parses the 64 hex characters produced by `to_hex`
+    pub fn from_hex(s: &str) -> Result<Hash, HashParseError> {
+        if s.len() != 64 {
+            return Err(HashParseError::InvalidLength(s.len()));
+        }
+        let mut data = [0u8; 32];
+        hex::decode_to_slice(s, &mut data).map_err(|_| HashParseError::InvalidHex)?;
+        Ok(Self(data))
+    }
+}
+
+impl FromStr for Hash {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashParseError {
+    /// Not 64 characters long
+    InvalidLength(usize),
+    /// Contains something other than hex digits
+    InvalidHex,
 }
 
+impl fmt::Display for HashParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashParseError::InvalidLength(len) => write!(f, "expected 64 hex characters, got {}", len),
+            HashParseError::InvalidHex => write!(f, "hash contains non-hex characters"),
+        }
+    }
+}
+
+impl std::error::Error for HashParseError {}
+
 impl fmt::Display for Hash {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.to_hex())
     }
 }
 
+// Hashes go over the wire as lowercase hex strings rather than byte arrays
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex_string = String::deserialize(deserializer)?;
+        Self::from_hex(&hex_string).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Inclusion proof for several leaves of one tree at once. Interior hashes
+/// shared between the leaves' paths appear once, and hashes the verifier can
+/// compute from the leaves themselves are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiProof {
+    /// Leaves in the whole tree, which fixes its shape
+    pub leaf_count: usize,
+    /// Proven leaf indices in ascending order; leaves are supplied to the
+    /// verifier in this order
+    pub indices: Vec<usize>,
+    /// Sibling hashes the verifier can't compute, level by level from the
+    /// leaves up and left to right within a level
+    pub hashes: Vec<Hash>,
+}
+
+/// Merkle tree over `Hash` leaves, hashed with `H` (SHA-256 by default)
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FastMerkleTree {
+#[serde(bound = "")]
+pub struct FastMerkleTree<H: Hasher = Sha256Hasher> {
     leaves: Vec<Hash>,
     nodes: Vec<Vec<Hash>>,
     root: Option<Hash>,
+    /// Position of each leaf, keyed by its prefixed hash; the first one
+    /// wins for duplicates
+    #[serde(default)]
+    leaf_index: HashMap<Hash, usize>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
 impl FastMerkleTree {
     pub fn new() -> Self {
-        Self {
-            leaves: Vec::new(),
-            nodes: Vec::new(),
-            root: None,
-        }
+        Self::with_hasher()
     }
 
     #[allow(dead_code)]
@@ -75,12 +205,69 @@ impl FastMerkleTree {
         tree.build();
         tree
     }
+}
+
+impl<H: Hasher> FastMerkleTree<H> {
+    /// An empty tree hashed with `H` rather than the default SHA-256
+    pub fn with_hasher() -> Self {
+        Self {
+            leaves: Vec::new(),
+            nodes: Vec::new(),
+            root: None,
+            leaf_index: HashMap::new(),
+            hasher: PhantomData,
+        }
+    }
 
     pub fn add_leaf(&mut self, leaf: Hash) {
-        self.leaves.push(leaf);
+        self.leaves.push(leaf.leaf_hash_with::<H>());
         self.root = None; // Invalidate root when adding new leaf
     }
 
+    /// Adds a leaf and keeps the root current by recomputing only the new
+    /// leaf's ancestors, O(log n) per leaf instead of a full `build()`
+    #[allow(dead_code)]
+    pub fn append(&mut self, leaf: Hash) {
+        // Bring the levels up to date with any leaves added via `add_leaf`
+        if self.root.is_none() && !self.leaves.is_empty() {
+            self.build();
+        }
+
+        let leaf = leaf.leaf_hash_with::<H>();
+        self.leaf_index.entry(leaf.clone()).or_insert(self.leaves.len());
+        self.leaves.push(leaf.clone());
+        if self.nodes.is_empty() {
+            self.nodes.push(Vec::new());
+        }
+        self.nodes[0].push(leaf);
+
+        let mut level = 0;
+        let mut index = self.nodes[0].len() - 1;
+
+        // Walk up until a level holds a single node, which is the root
+        while self.nodes[level].len() > 1 {
+            let parent_index = index / 2;
+            let left = &self.nodes[level][parent_index * 2];
+            let right = self.nodes[level].get(parent_index * 2 + 1).unwrap_or(&EMPTY_SIBLING);
+            let parent = left.combine_with::<H>(right);
+
+            if self.nodes.len() == level + 1 {
+                self.nodes.push(Vec::new());
+            }
+            let parents = &mut self.nodes[level + 1];
+            if parent_index < parents.len() {
+                parents[parent_index] = parent;
+            } else {
+                parents.push(parent);
+            }
+
+            level += 1;
+            index = parent_index;
+        }
+
+        self.root = Some(self.nodes[level][0].clone());
+    }
+
     pub fn build(&mut self) {
         if self.leaves.is_empty() {
             self.root = None;
@@ -88,23 +275,15 @@ impl FastMerkleTree {
         }
 
         self.nodes.clear();
+        self.leaf_index.clear();
+        for (index, leaf) in self.leaves.iter().enumerate() {
+            self.leaf_index.entry(leaf.clone()).or_insert(index);
+        }
         let mut current_level = self.leaves.clone();
 
         // Build tree bottom-up
         while current_level.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            // Process pairs of nodes
-            for chunk in current_level.chunks(2) {
-                let combined = if chunk.len() == 2 {
-                    chunk[0].combine(&chunk[1])
-                } else {
-                    // For odd number of nodes, duplicate the last one
-                    chunk[0].combine(&chunk[0])
-                };
-                next_level.push(combined);
-            }
-            
+            let next_level = Self::parent_level(&current_level);
             self.nodes.push(current_level);
             current_level = next_level;
         }
@@ -115,10 +294,48 @@ impl FastMerkleTree {
         }
     }
 
+    /// Hashes each pair of nodes in `level` into the level above
+    fn parent_level(level: &[Hash]) -> Vec<Hash> {
+        level
+            .chunks(2)
+            .map(|chunk| match chunk {
+                [left, right] => left.combine_with::<H>(right),
+                // Pair the odd node with an empty sibling rather than
+                // itself, so [a, b, c] and [a, b, c, c] differ
+                [odd] => odd.combine_with::<H>(&EMPTY_SIBLING),
+                _ => unreachable!("chunks(2) yields one or two nodes"),
+            })
+            .collect()
+    }
+
     pub fn get_root(&self) -> Option<&Hash> {
         self.root.as_ref()
     }
 
+    /// The root `build` would give, computed without storing the levels
+    /// if the tree hasn't been built since its last leaf was added
+    fn current_root(&self) -> Option<Hash> {
+        if self.root.is_some() || self.leaves.is_empty() {
+            return self.root.clone();
+        }
+        let mut level = self.leaves.clone();
+        while level.len() > 1 {
+            level = Self::parent_level(&level);
+        }
+        level.pop()
+    }
+
+    /// Whether the two trees commit to the same leaves in the same order,
+    /// judged by their roots alone. Empty trees commit to nothing, so they
+    /// never compare equal.
+    #[allow(dead_code)]
+    pub fn root_eq(&self, other: &FastMerkleTree<H>) -> bool {
+        match (self.current_root(), other.current_root()) {
+            (Some(root), Some(other_root)) => root == other_root,
+            _ => false,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn get_proof(&self, index: usize) -> Option<Vec<Hash>> {
         if index >= self.leaves.len() || self.nodes.is_empty() {
@@ -128,13 +345,14 @@ impl FastMerkleTree {
         let mut proof = Vec::new();
         let mut current_index = index;
 
-        // Traverse from leaf to root, collecting sibling hashes
-        for level in &self.nodes {
+        // Traverse from leaf to root, collecting sibling hashes (the root
+        // level has no sibling)
+        for level in &self.nodes[..self.nodes.len() - 1] {
             if current_index >= level.len() {
                 break;
             }
 
-            let sibling_index = if current_index % 2 == 0 {
+            let sibling_index = if current_index.is_multiple_of(2) {
                 current_index + 1
             } else {
                 current_index - 1
@@ -143,8 +361,8 @@ impl FastMerkleTree {
             if sibling_index < level.len() {
                 proof.push(level[sibling_index].clone());
             } else if current_index < level.len() {
-                // For odd number of nodes, sibling is the node itself
-                proof.push(level[current_index].clone());
+                // The odd node out was paired with an empty sibling
+                proof.push(EMPTY_SIBLING);
             }
 
             current_index /= 2;
@@ -155,28 +373,64 @@ impl FastMerkleTree {
 
     #[allow(dead_code)]
     pub fn verify_proof(&self, leaf: &Hash, proof: &[Hash], index: usize) -> bool {
-        if let Some(root) = &self.root {
-            let calculated_root = self.calculate_root_from_proof(leaf, proof, index);
-            calculated_root == *root
-        } else {
-            false
+        match &self.root {
+            Some(root) => calculate_root_from_proof::<H>(leaf, proof, index) == *root,
+            None => false,
         }
     }
 
+    /// One proof covering every leaf in `indices`, which may be in any order
+    /// and contain repeats. `None` if there are no indices, any is out of
+    /// range, or the tree hasn't been built.
     #[allow(dead_code)]
-    fn calculate_root_from_proof(&self, leaf: &Hash, proof: &[Hash], mut index: usize) -> Hash {
-        let mut current_hash = leaf.clone();
+    pub fn get_multiproof(&self, indices: &[usize]) -> Option<MultiProof> {
+        if indices.is_empty() || self.root.is_none() || indices.iter().any(|&index| index >= self.leaves.len()) {
+            return None;
+        }
 
-        for proof_hash in proof {
-            current_hash = if index % 2 == 0 {
-                current_hash.combine(proof_hash)
-            } else {
-                proof_hash.combine(&current_hash)
-            };
-            index /= 2;
+        let mut known = indices.to_vec();
+        known.sort_unstable();
+        known.dedup();
+        let proven = known.clone();
+        let mut hashes = Vec::new();
+
+        for level in &self.nodes[..self.nodes.len() - 1] {
+            let mut parents = Vec::new();
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if index.is_multiple_of(2) {
+                    if known.get(i + 1) == Some(&(index + 1)) {
+                        // Both children are known, so the parent is too
+                        i += 1;
+                    } else if let Some(sibling) = level.get(index + 1) {
+                        hashes.push(sibling.clone());
+                    }
+                    // Otherwise the odd node out's empty sibling is implied
+                } else {
+                    hashes.push(level[index - 1].clone());
+                }
+                parents.push(index / 2);
+                i += 1;
+            }
+            known = parents;
         }
 
-        current_hash
+        Some(MultiProof {
+            leaf_count: self.leaves.len(),
+            indices: proven,
+            hashes,
+        })
+    }
+
+    /// Checks that `leaves`, in the order of `proof.indices`, are all in
+    /// this tree
+    #[allow(dead_code)]
+    pub fn verify_multiproof(&self, leaves: &[Hash], proof: &MultiProof) -> bool {
+        match &self.root {
+            Some(root) => calculate_root_from_multiproof::<H>(leaves, proof).as_ref() == Some(root),
+            None => false,
+        }
     }
 
     #[allow(dead_code)]
@@ -188,6 +442,101 @@ impl FastMerkleTree {
     pub fn is_empty(&self) -> bool {
         self.leaves.is_empty()
     }
+
+    /// Index of `leaf` as of the last build, the first one if it was added
+    /// more than once
+    #[allow(dead_code)]
+    pub fn index_of(&self, leaf: &Hash) -> Option<usize> {
+        self.leaf_index.get(&leaf.leaf_hash_with::<H>()).copied()
+    }
+
+    /// Levels in the tree as of the last build, leaves and root included: 0
+    /// when empty, 1 for a lone leaf. Proofs hold `depth() - 1` hashes.
+    #[allow(dead_code)]
+    pub fn depth(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Nodes across all levels as of the last build, leaves and root included
+    #[allow(dead_code)]
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().map(|level| level.len()).sum()
+    }
+}
+
+/// Checks an inclusion proof against a trusted root without needing the tree
+pub fn verify_proof_against_root(leaf: &Hash, proof: &[Hash], index: usize, root: &Hash) -> bool {
+    calculate_root_from_proof::<Sha256Hasher>(leaf, proof, index) == *root
+}
+
+/// Checks a multiproof against a trusted root without needing the tree
+#[allow(dead_code)]
+pub fn verify_multiproof_against_root(leaves: &[Hash], proof: &MultiProof, root: &Hash) -> bool {
+    calculate_root_from_multiproof::<Sha256Hasher>(leaves, proof).as_ref() == Some(root)
+}
+
+/// Root the proven leaves hash up to, or `None` if the proof is malformed
+fn calculate_root_from_multiproof<H: Hasher>(leaves: &[Hash], proof: &MultiProof) -> Option<Hash> {
+    let indices_valid = !proof.indices.is_empty()
+        && proof.indices.windows(2).all(|pair| pair[0] < pair[1])
+        && proof.indices.last().is_some_and(|&last| last < proof.leaf_count);
+    if !indices_valid || leaves.len() != proof.indices.len() {
+        return None;
+    }
+
+    let mut level: Vec<(usize, Hash)> = proof
+        .indices
+        .iter()
+        .zip(leaves)
+        .map(|(&index, leaf)| (index, leaf.leaf_hash_with::<H>()))
+        .collect();
+    let mut level_len = proof.leaf_count;
+    let mut hashes = proof.hashes.iter();
+
+    while level_len > 1 {
+        let mut parents = Vec::new();
+        let mut i = 0;
+        while i < level.len() {
+            let (index, hash) = &level[i];
+            let parent = if index.is_multiple_of(2) {
+                if level.get(i + 1).is_some_and(|(next, _)| *next == index + 1) {
+                    i += 1;
+                    hash.combine_with::<H>(&level[i].1)
+                } else if index + 1 >= level_len {
+                    hash.combine_with::<H>(&EMPTY_SIBLING)
+                } else {
+                    hash.combine_with::<H>(hashes.next()?)
+                }
+            } else {
+                hashes.next()?.combine_with::<H>(hash)
+            };
+            parents.push((index / 2, parent));
+            i += 1;
+        }
+        level = parents;
+        level_len = level_len.div_ceil(2);
+    }
+
+    // Every supplied hash must have been used
+    if hashes.next().is_some() {
+        return None;
+    }
+    level.pop().map(|(_, root)| root)
+}
+
+fn calculate_root_from_proof<H: Hasher>(leaf: &Hash, proof: &[Hash], mut index: usize) -> Hash {
+    let mut current_hash = leaf.leaf_hash_with::<H>();
+
+    for proof_hash in proof {
+        current_hash = if index.is_multiple_of(2) {
+            current_hash.combine_with::<H>(proof_hash)
+        } else {
+            proof_hash.combine_with::<H>(&current_hash)
+        };
+        index /= 2;
+    }
+
+    current_hash
 }
 
 #[cfg(test)]
@@ -226,4 +575,301 @@ mod tests {
         let proof = tree.get_proof(0).unwrap();
         assert!(tree.verify_proof(&leaf, &proof, 0));
     }
+
+    #[test]
+    fn test_hash_serializes_as_hex() {
+        let hash = Hash::from_string("serde");
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", hash.to_hex()));
+
+        let parsed: Hash = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, hash);
+
+        // Wrong length, non-hex digits and the old byte-array form are rejected
+        assert!(serde_json::from_str::<Hash>("\"abcd\"").is_err());
+        assert!(serde_json::from_str::<Hash>(&format!("\"{}\"", "zz".repeat(32))).is_err());
+        assert!(serde_json::from_str::<Hash>(&serde_json::to_string(&[0u8; 32]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_hash_from_hex() {
+        let hash = Hash::from_string("parse me");
+        assert_eq!(Hash::from_hex(&hash.to_hex()), Ok(hash.clone()));
+        assert_eq!(hash.to_hex().parse::<Hash>(), Ok(hash.clone()));
+        assert_eq!(Hash::from_hex(&hash.to_hex().to_uppercase()), Ok(hash));
+
+        assert_eq!(Hash::from_hex(""), Err(HashParseError::InvalidLength(0)));
+        assert_eq!(Hash::from_hex(&"a".repeat(63)), Err(HashParseError::InvalidLength(63)));
+        assert_eq!(Hash::from_hex(&"a".repeat(66)), Err(HashParseError::InvalidLength(66)));
+        assert_eq!(Hash::from_hex(&"g".repeat(64)), Err(HashParseError::InvalidHex));
+    }
+
+    #[test]
+    fn test_append_matches_build() {
+        let mut incremental = FastMerkleTree::new();
+
+        for n in 1..=40 {
+            let leaf = Hash::from_string(&format!("leaf{}", n));
+            incremental.append(leaf);
+
+            let mut batch = FastMerkleTree::new();
+            for i in 1..=n {
+                batch.add_leaf(Hash::from_string(&format!("leaf{}", i)));
+            }
+            batch.build();
+
+            assert_eq!(incremental.get_root(), batch.get_root(), "root differs at {} leaves", n);
+            assert_eq!(incremental.nodes, batch.nodes, "levels differ at {} leaves", n);
+        }
+
+        // Appending after add_leaf picks up the pending leaves too
+        let mut mixed = FastMerkleTree::new();
+        mixed.add_leaf(Hash::from_string("leaf1"));
+        mixed.add_leaf(Hash::from_string("leaf2"));
+        mixed.append(Hash::from_string("leaf3"));
+        let batch = FastMerkleTree::from_data(vec!["leaf1".to_string(), "leaf2".to_string(), "leaf3".to_string()]);
+        assert_eq!(mixed.get_root(), batch.get_root());
+        let proof = mixed.get_proof(2).unwrap();
+        assert!(mixed.verify_proof(&Hash::from_string("leaf3"), &proof, 2));
+    }
+
+    #[test]
+    fn test_crafted_leaves_cannot_forge_root() {
+        let leaves: Vec<Hash> = (0..4).map(|i| Hash::from_string(&format!("tx{}", i))).collect();
+        let mut tree = FastMerkleTree::new();
+        for leaf in &leaves {
+            tree.add_leaf(leaf.clone());
+        }
+        tree.build();
+        let root = tree.get_root().unwrap().clone();
+
+        // Presenting the internal nodes as a two-transaction list
+        let mut forged = FastMerkleTree::new();
+        forged.add_leaf(tree.nodes[1][0].clone());
+        forged.add_leaf(tree.nodes[1][1].clone());
+        forged.build();
+        assert_ne!(forged.get_root(), Some(&root));
+
+        // ...or proving an internal node as if it were a leaf
+        assert!(!tree.verify_proof(&tree.nodes[1][0], &[tree.nodes[1][1].clone()], 0));
+
+        // Duplicating the odd last transaction no longer yields the same root
+        let mut odd = FastMerkleTree::new();
+        let mut padded = FastMerkleTree::new();
+        for leaf in &leaves[..3] {
+            odd.add_leaf(leaf.clone());
+            padded.add_leaf(leaf.clone());
+        }
+        padded.add_leaf(leaves[2].clone());
+        odd.build();
+        padded.build();
+        assert_ne!(odd.get_root(), padded.get_root());
+
+        // Proofs for the odd node out still verify
+        let proof = odd.get_proof(2).unwrap();
+        assert!(odd.verify_proof(&leaves[2], &proof, 2));
+    }
+
+    #[test]
+    fn test_verify_proof_against_root() {
+        let leaves: Vec<Hash> = (0..7).map(|i| Hash::from_string(&format!("leaf{}", i))).collect();
+        let mut tree = FastMerkleTree::new();
+        for leaf in &leaves {
+            tree.add_leaf(leaf.clone());
+        }
+        tree.build();
+        let root = tree.get_root().unwrap().clone();
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.get_proof(index).unwrap();
+            assert!(verify_proof_against_root(leaf, &proof, index, &root));
+            assert!(!verify_proof_against_root(leaf, &proof, index ^ 1, &root));
+            assert!(!verify_proof_against_root(&Hash::from_string("other"), &proof, index, &root));
+        }
+
+        let proof = tree.get_proof(0).unwrap();
+        assert!(!verify_proof_against_root(&leaves[0], &proof, 0, &Hash::from_string("wrong root")));
+    }
+
+    // A second digest to plug in, just to tell the trees apart
+    struct Sha512_256Hasher;
+
+    impl Hasher for Sha512_256Hasher {
+        fn digest(parts: &[&[u8]]) -> [u8; 32] {
+            digest_parts::<sha2::Sha512_256>(parts)
+        }
+    }
+
+    #[test]
+    fn test_pluggable_hasher() {
+        let leaves: Vec<Hash> = (0..5).map(|i| Hash::from_string(&format!("leaf{}", i))).collect();
+        let mut sha256 = FastMerkleTree::new();
+        let mut sha512_256: FastMerkleTree<Sha512_256Hasher> = FastMerkleTree::with_hasher();
+        for leaf in &leaves {
+            sha256.add_leaf(leaf.clone());
+            sha512_256.add_leaf(leaf.clone());
+        }
+        sha256.build();
+        sha512_256.build();
+
+        assert_ne!(sha256.get_root(), sha512_256.get_root());
+        assert_ne!(Hash::from_bytes(b"data"), Hash::from_bytes_with::<Sha512_256Hasher>(b"data"));
+
+        // Proofs verify under the hasher that made them, and only that one
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = sha256.get_proof(index).unwrap();
+            assert!(sha256.verify_proof(leaf, &proof, index));
+            assert!(!sha512_256.verify_proof(leaf, &proof, index));
+
+            let proof = sha512_256.get_proof(index).unwrap();
+            assert!(sha512_256.verify_proof(leaf, &proof, index));
+            assert!(!sha256.verify_proof(leaf, &proof, index));
+        }
+    }
+
+    #[test]
+    fn test_multiproof() {
+        // Small xorshift generator, so the index sets vary but are repeatable
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next_random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for leaf_count in [1usize, 2, 3, 7, 8, 13, 32, 33] {
+            let leaves: Vec<Hash> = (0..leaf_count).map(|i| Hash::from_string(&format!("leaf{}", i))).collect();
+            let mut tree = FastMerkleTree::new();
+            for leaf in &leaves {
+                tree.add_leaf(leaf.clone());
+            }
+            tree.build();
+            let root = tree.get_root().unwrap().clone();
+
+            for _ in 0..20 {
+                let picks = 1 + (next_random() as usize) % leaf_count;
+                let indices: Vec<usize> = (0..picks).map(|_| (next_random() as usize) % leaf_count).collect();
+                let proof = tree.get_multiproof(&indices).unwrap();
+                let proven: Vec<Hash> = proof.indices.iter().map(|&i| leaves[i].clone()).collect();
+
+                assert!(tree.verify_multiproof(&proven, &proof));
+                assert!(verify_multiproof_against_root(&proven, &proof, &root));
+
+                // Never more hashes than the separate proofs would carry
+                let separate: usize = proof.indices.iter().map(|&i| tree.get_proof(i).unwrap().len()).sum();
+                assert!(proof.hashes.len() <= separate);
+
+                // A swapped leaf, a dropped hash or an extra hash all fail
+                let mut tampered = proven.clone();
+                tampered[0] = Hash::from_string("other");
+                assert!(!tree.verify_multiproof(&tampered, &proof));
+                if !proof.hashes.is_empty() {
+                    let mut short = proof.clone();
+                    short.hashes.pop();
+                    assert!(!tree.verify_multiproof(&proven, &short));
+                }
+                let mut long = proof.clone();
+                long.hashes.push(Hash::from_string("extra"));
+                assert!(!tree.verify_multiproof(&proven, &long));
+            }
+        }
+
+        let tree = FastMerkleTree::from_data((0..8).map(|i| format!("leaf{}", i)).collect());
+        let proof = tree.get_multiproof(&[0, 1, 2, 3]).unwrap();
+        assert_eq!(proof.hashes.len(), 1);
+        assert!(tree.get_multiproof(&[]).is_none());
+        assert!(tree.get_multiproof(&[8]).is_none());
+    }
+
+    #[test]
+    fn test_depth_and_node_count() {
+        let empty = FastMerkleTree::new();
+        assert_eq!(empty.depth(), 0);
+        assert_eq!(empty.node_count(), 0);
+
+        let single = FastMerkleTree::from_data(vec!["only".to_string()]);
+        assert_eq!(single.depth(), 1);
+        assert_eq!(single.node_count(), 1);
+
+        // 100 leaves halve, rounding up, to 50, 25, 13, 7, 4, 2 and the root:
+        // eight levels, so every proof carries seven hashes
+        let tree = FastMerkleTree::from_data((0..100).map(|i| format!("leaf{}", i)).collect());
+        assert_eq!(tree.depth(), 8);
+        assert_eq!(tree.node_count(), 100 + 50 + 25 + 13 + 7 + 4 + 2 + 1);
+        for index in [0, 57, 99] {
+            assert_eq!(tree.get_proof(index).unwrap().len(), tree.depth() - 1);
+        }
+    }
+
+    #[test]
+    fn test_hash_as_key() {
+        use std::collections::{BTreeMap, HashSet};
+
+        let hashes: Vec<Hash> = (0..50).map(|i| Hash::from_string(&i.to_string())).collect();
+
+        let set: HashSet<Hash> = hashes.iter().cloned().chain(hashes.iter().cloned()).collect();
+        assert_eq!(set.len(), hashes.len());
+        assert!(set.contains(&Hash::from_string("7")));
+        assert!(!set.contains(&Hash::from_string("50")));
+
+        // Sorted the same as the hex strings
+        let map: BTreeMap<Hash, usize> = hashes.iter().cloned().enumerate().map(|(i, hash)| (hash, i)).collect();
+        let keys: Vec<String> = map.keys().map(Hash::to_hex).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert_eq!(map[&hashes[7]], 7);
+        let mut bytes = [0u8; 32];
+        bytes[2] = 1;
+        assert!(Hash::zero() < Hash::new(bytes));
+    }
+
+    #[test]
+    fn test_root_eq() {
+        let data: Vec<String> = (0..10).map(|i| format!("tx{}", i)).collect();
+        let built = FastMerkleTree::from_data(data.clone());
+
+        // An unbuilt tree over the same data is still recognised
+        let mut unbuilt = FastMerkleTree::new();
+        for item in &data {
+            unbuilt.add_leaf(Hash::from_string(item));
+        }
+        assert!(built.root_eq(&unbuilt));
+        assert!(unbuilt.root_eq(&built));
+        assert!(unbuilt.get_root().is_none());
+
+        let mut reordered = data.clone();
+        reordered.swap(0, 1);
+        assert!(!built.root_eq(&FastMerkleTree::from_data(reordered)));
+        assert!(!built.root_eq(&FastMerkleTree::from_data(data[..9].to_vec())));
+
+        let empty = FastMerkleTree::new();
+        assert!(!empty.root_eq(&FastMerkleTree::new()));
+        assert!(!built.root_eq(&empty));
+        assert!(!empty.root_eq(&built));
+    }
+
+    #[test]
+    fn test_index_of() {
+        let data: Vec<String> = ["a", "b", "c", "b"].iter().map(|s| s.to_string()).collect();
+        let tree = FastMerkleTree::from_data(data);
+        assert_eq!(tree.index_of(&Hash::from_string("a")), Some(0));
+        assert_eq!(tree.index_of(&Hash::from_string("c")), Some(2));
+        assert_eq!(tree.index_of(&Hash::from_string("d")), None);
+
+        // Duplicates resolve to the first occurrence
+        assert_eq!(tree.index_of(&Hash::from_string("b")), Some(1));
+
+        // Appended leaves are indexed straight away
+        let mut appended = FastMerkleTree::new();
+        for leaf in ["x", "y", "x"] {
+            appended.append(Hash::from_string(leaf));
+        }
+        assert_eq!(appended.index_of(&Hash::from_string("x")), Some(0));
+        assert_eq!(appended.index_of(&Hash::from_string("y")), Some(1));
+
+        let proof = tree.get_proof(tree.index_of(&Hash::from_string("c")).unwrap()).unwrap();
+        assert!(tree.verify_proof(&Hash::from_string("c"), &proof, 2));
+    }
 }
@@ -1,5 +1,7 @@
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
@@ -18,14 +20,40 @@ pub enum GameResult {
 }
 
 impl Move {
-    pub fn from_seed(seed: u64) -> Self {
-        match seed % 3 {
+    fn from_index(index: u32) -> Self {
+        match index {
             0 => Move::Rock,
             1 => Move::Paper,
             _ => Move::Scissors,
         }
     }
 
+    /// Picks a move from a digest by rejection sampling: the first byte
+    /// below 255, reduced modulo 3. Those bytes are uniform over 0..=254,
+    /// which divides evenly into three, so no move is favoured. A digest of
+    /// nothing but 255s is hashed again. Every move in mining, the players'
+    /// and the blockchain's, comes through here.
+    pub fn from_hash(hash: &[u8]) -> Self {
+        let mut rehashed;
+        let mut digest = hash;
+        loop {
+            if let Some(&byte) = digest.iter().find(|&&byte| byte < 255) {
+                return Self::from_index(byte as u32 % 3);
+            }
+            rehashed = Sha256::digest(digest);
+            digest = &rehashed;
+        }
+    }
+
+    /// The move that beats this one
+    pub fn counter(&self) -> Move {
+        match self {
+            Move::Rock => Move::Paper,
+            Move::Paper => Move::Scissors,
+            Move::Scissors => Move::Rock,
+        }
+    }
+
     pub fn beats(&self, other: &Move) -> GameResult {
         match (self, other) {
             (Move::Rock, Move::Scissors) => GameResult::PlayerWin,
@@ -39,6 +67,90 @@ impl Move {
     }
 }
 
+/// Outcomes of a batch of games, from the players' side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct GameTally {
+    player_wins: u64,
+    blockchain_wins: u64,
+    ties: u64,
+}
+
+impl GameTally {
+    fn record(&mut self, result: GameResult) {
+        match result {
+            GameResult::PlayerWin => self.player_wins += 1,
+            GameResult::BlockchainWin => self.blockchain_wins += 1,
+            GameResult::Tie => self.ties += 1,
+        }
+    }
+
+    fn games(&self) -> u64 {
+        self.player_wins + self.blockchain_wins + self.ties
+    }
+}
+
+impl std::ops::AddAssign for GameTally {
+    fn add_assign(&mut self, other: Self) {
+        self.player_wins += other.player_wins;
+        self.blockchain_wins += other.blockchain_wins;
+        self.ties += other.ties;
+    }
+}
+
+/// Called once per mining round with the round number and the number of
+/// players still short of their required wins
+pub type ProgressCallback<'a> = &'a mut dyn FnMut(u32, usize);
+
+/// Takes the first 8 bytes of a digest as a big-endian seed
+fn seed_from_hash(hash: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// How a player picks its moves. Mined blocks are checked by replaying their
+/// games with `SeededStrategy`, so any other strategy is for experiments:
+/// blocks mined with it won't verify.
+pub trait PlayStrategy: fmt::Debug + Send + Sync {
+    /// The move `player` makes in its next game of `round`
+    fn choose_move(&self, player: &Player, round: u32, block_seed: u64) -> Move;
+}
+
+/// Reseeds every game from the player's seed, its game count, the round and
+/// the block seed, so the sequence can't be predicted without the seeds but
+/// can be replayed by anyone who has them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeededStrategy;
+
+impl PlayStrategy for SeededStrategy {
+    fn choose_move(&self, player: &Player, round: u32, block_seed: u64) -> Move {
+        Move::from_hash(&player.game_digest(round, block_seed))
+    }
+}
+
+/// Plays whatever would have beaten the blockchain's last move
+/// `bias_percent` of the time, and the seeded move otherwise
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct CounterStrategy {
+    pub bias_percent: u8,
+}
+
+impl PlayStrategy for CounterStrategy {
+    fn choose_move(&self, player: &Player, round: u32, block_seed: u64) -> Move {
+        let digest = player.game_digest(round, block_seed);
+        match player.last_blockchain_move {
+            // The last digest byte decides, leaving the rest to the seeded move
+            Some(last) if digest[31] % 100 < self.bias_percent => last.counter(),
+            _ => Move::from_hash(&digest),
+        }
+    }
+}
+
+fn default_strategy() -> Arc<dyn PlayStrategy> {
+    Arc::new(SeededStrategy)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: u32,
@@ -46,6 +158,11 @@ pub struct Player {
     pub current_wins: u32,
     pub games_played: u32,
     pub seed: u64,
+    /// The blockchain's move in this player's most recent game
+    #[serde(default)]
+    pub last_blockchain_move: Option<Move>,
+    #[serde(skip, default = "default_strategy")]
+    strategy: Arc<dyn PlayStrategy>,
 }
 
 impl Player {
@@ -54,11 +171,7 @@ impl Player {
         let mut hasher = Sha256::new();
         hasher.update(base_seed.to_be_bytes());
         hasher.update(id.to_be_bytes());
-        let hash = hasher.finalize();
-        let seed = u64::from_be_bytes([
-            hash[0], hash[1], hash[2], hash[3],
-            hash[4], hash[5], hash[6], hash[7],
-        ]);
+        let seed = seed_from_hash(&hasher.finalize());
 
         Self {
             id,
@@ -66,15 +179,39 @@ impl Player {
             current_wins: 0,
             games_played: 0,
             seed,
+            last_blockchain_move: None,
+            strategy: default_strategy(),
         }
     }
 
-    pub fn play_game(&mut self, blockchain_move: Move) -> GameResult {
+    /// Plays with `strategy` instead of `SeededStrategy`
+    pub fn with_strategy(mut self, strategy: Arc<dyn PlayStrategy>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Digest of the player's seed, its game count, the round and the block
+    /// seed, unique to each game, for strategies to draw on
+    pub fn game_digest(&self, round: u32, block_seed: u64) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seed.to_be_bytes());
+        hasher.update(self.games_played.to_be_bytes());
+        hasher.update(round.to_be_bytes());
+        hasher.update(block_seed.to_be_bytes());
+        hasher.finalize().into()
+    }
+
+    /// The move this player's strategy makes in its next game
+    pub fn next_move(&self, round: u32, block_seed: u64) -> Move {
+        self.strategy.choose_move(self, round, block_seed)
+    }
+
+    pub fn play_game(&mut self, blockchain_move: Move, round: u32, block_seed: u64) -> GameResult {
         // Generate player move based on current state
-        let game_seed = self.seed.wrapping_add(self.games_played as u64);
-        let player_move = Move::from_seed(game_seed);
+        let player_move = self.next_move(round, block_seed);
         
         self.games_played += 1;
+        self.last_blockchain_move = Some(blockchain_move);
         
         let result = player_move.beats(&blockchain_move);
         if result == GameResult::PlayerWin {
@@ -88,9 +225,11 @@ impl Player {
         self.current_wins >= self.required_wins
     }
 
+    #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.current_wins = 0;
         self.games_played = 0;
+        self.last_blockchain_move = None;
     }
 }
 
@@ -98,36 +237,148 @@ impl Player {
 pub struct RPSMiningConfig {
     pub total_players: u32,
     pub blocks_mined: u32,
+    /// Split each round's player simulation across threads, once there are
+    /// enough players for it to pay off
+    pub parallel: bool,
+    /// Block time the difficulty adjustment aims for
+    pub target_block_time_ms: u128,
+    /// Difficulty levels added on top of `blocks_mined` by retargeting
+    pub difficulty_adjustment: i64,
+    /// How many recent block times retargeting averages over
+    pub retarget_window: usize,
+    /// Times of the last `retarget_window` blocks, oldest first
+    #[serde(default)]
+    pub recent_block_times_ms: VecDeque<u128>,
+    /// Rounds played before mining gives up on a block
+    pub max_rounds: u32,
+    /// Wall-clock time mining may spend on a block, unbounded if `None`
+    #[serde(default)]
+    pub max_mining_time: Option<Duration>,
+    /// Games played on a single block before mining is aborted
+    pub max_total_games: u64,
+    /// Wins every player needs at difficulty level 0
+    #[serde(default = "default_base_required_wins")]
+    pub base_required_wins: u32,
+    /// Extra wins each difficulty level asks of one more player. 0 keeps
+    /// every player at `base_required_wins` however many blocks are mined.
+    #[serde(default = "default_difficulty_step")]
+    pub difficulty_step: u32,
+}
+
+fn default_base_required_wins() -> u32 {
+    1
+}
+
+fn default_difficulty_step() -> u32 {
+    1
 }
 
 impl RPSMiningConfig {
+    /// The standard configuration. Its difficulty curve is the one chains
+    /// have always been mined with, one win each to start and one more
+    /// player needing a second win per level, so existing chains still verify.
     pub fn new() -> Self {
         Self {
             total_players: 100,
             blocks_mined: 0,
+            parallel: true,
+            target_block_time_ms: 10_000,
+            difficulty_adjustment: 0,
+            retarget_window: 5,
+            recent_block_times_ms: VecDeque::new(),
+            max_rounds: 1_000_000,
+            max_mining_time: None,
+            max_total_games: 10_000_000,
+            base_required_wins: default_base_required_wins(),
+            difficulty_step: default_difficulty_step(),
+        }
+    }
+
+    /// Same defaults with `total_players` players instead of 100. Mining
+    /// always needs at least one player, so 0 is treated as 1.
+    #[allow(dead_code)]
+    pub fn with_players(total_players: u32) -> Self {
+        Self {
+            total_players: total_players.max(1),
+            ..Self::new()
+        }
+    }
+
+    /// Retargets difficulty after a block took `last_block_time_ms` to mine,
+    /// from the average time of the last `retarget_window` blocks. An average
+    /// faster than `target_ms` raises the difficulty level and a slower one
+    /// lowers it, one level per halving/doubling of the block time. Like
+    /// Bitcoin's retarget, the correction is clamped to a factor of 4.
+    pub fn retarget(&mut self, last_block_time_ms: u128, target_ms: u128) {
+        self.recent_block_times_ms.push_back(last_block_time_ms);
+        while self.recent_block_times_ms.len() > self.retarget_window.max(1) {
+            self.recent_block_times_ms.pop_front();
+        }
+
+        if target_ms == 0 {
+            return;
+        }
+
+        let average_ms = self.recent_block_times_ms.iter().sum::<u128>() / self.recent_block_times_ms.len() as u128;
+        let ratio = (target_ms as f64 / average_ms.max(1) as f64).clamp(0.25, 4.0);
+        self.difficulty_adjustment += ratio.log2().round() as i64;
+
+        // Never retarget below the starting difficulty
+        self.difficulty_adjustment = self.difficulty_adjustment.max(-(self.blocks_mined as i64));
+    }
+
+    /// Retargets difficulty after a block took `actual_ms` to mine. Same as
+    /// `retarget`, so the block time counts towards the recent average.
+    #[allow(dead_code)]
+    pub fn adjust_for_block_time(&mut self, actual_ms: u128, target_ms: u128) {
+        self.retarget(actual_ms, target_ms);
+    }
+
+    /// Difficulty level the win requirements are derived from
+    fn difficulty_level(&self) -> u32 {
+        (self.blocks_mined as i64 + self.difficulty_adjustment).clamp(0, u32::MAX as i64) as u32
+    }
+
+    /// The difficulty the next block is mined at under this config
+    pub fn difficulty_info(&self) -> DifficultyInfo {
+        let requirements = self.get_win_requirements();
+        let total_required_wins: u32 = requirements.iter().sum();
+        
+        let mut win_distribution = HashMap::new();
+        for &wins in &requirements {
+            *win_distribution.entry(wins).or_insert(0) += 1;
+        }
+
+        DifficultyInfo {
+            block_number: self.blocks_mined,
+            total_required_wins,
+            win_distribution,
+            total_players: self.total_players,
         }
     }
 
     pub fn get_win_requirements(&self) -> Vec<u32> {
         let mut requirements = Vec::new();
-        let blocks = self.blocks_mined;
+        let blocks = self.difficulty_level();
+        let total_players = self.total_players;
+        let base = self.base_required_wins;
         
-        if blocks == 0 {
-            // First block: all 100 players need 1 win
-            requirements.resize(100, 1);
+        if blocks == 0 || self.difficulty_step == 0 {
+            // First block, or a difficulty that never ratchets: all players need the base wins
+            requirements.resize(total_players as usize, base);
         } else {
             // Each subsequent block increases difficulty
-            let players_with_extra_wins = std::cmp::min(blocks, 100);
-            let players_with_one_win = 100 - players_with_extra_wins;
+            let players_with_extra_wins = std::cmp::min(blocks, total_players);
+            let players_with_base_wins = total_players - players_with_extra_wins;
             
-            // Players that need only 1 win
-            for _ in 0..players_with_one_win {
-                requirements.push(1);
-            }
+            // Players that need only the base wins
+            requirements.resize(players_with_base_wins as usize, base);
             
-            // Players that need multiple wins
+            // Players that need extra wins
             for i in 0..players_with_extra_wins {
-                requirements.push(2 + (i / 100)); // Increment every 100 blocks
+                // Increment every `total_players` blocks
+                let steps = 1 + i / total_players;
+                requirements.push(base.saturating_add(self.difficulty_step.saturating_mul(steps)));
             }
         }
         
@@ -141,80 +392,94 @@ pub struct RPSMiner {
     pub players: Vec<Player>,
     pub blockchain_seed: u64,
     pub games_played: u64,
+    /// What this miner's players play with; see `PlayStrategy`
+    #[serde(skip, default = "default_strategy")]
+    strategy: Arc<dyn PlayStrategy>,
 }
 
+/// Fewest players a thread is given when a round is split across cores.
+/// Starting and joining a scoped thread costs around 15µs, while a player's
+/// round costs 300-900ns, so a thread needs a few hundred players before the
+/// spawn is paid for; `bench_round_threading` measures both. The default 100
+/// players are always played on the calling thread.
+const MIN_PLAYERS_PER_THREAD: usize = 512;
+
 impl RPSMiner {
+    /// Creates a miner on the default network seed. Every node on the same
+    /// network seed mines, and can replay, exactly the same games for a block.
     pub fn new(config: RPSMiningConfig) -> Self {
-        let blockchain_seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
+        Self::with_seed(config, 0)
+    }
 
-        let win_requirements = config.get_win_requirements();
-        let mut players = Vec::new();
-        
-        for (i, &required_wins) in win_requirements.iter().enumerate() {
-            players.push(Player::new(i as u32, required_wins, blockchain_seed));
-        }
+    /// Creates a miner for a different network, salted with `blockchain_seed`
+    pub fn with_seed(config: RPSMiningConfig, blockchain_seed: u64) -> Self {
+        let strategy = default_strategy();
+        let players = Self::create_players(&config.get_win_requirements(), blockchain_seed, &strategy);
 
         Self {
             config,
             players,
             blockchain_seed,
             games_played: 0,
+            strategy,
         }
     }
 
-    pub fn mine_block(&mut self, block_data: &str) -> Result<RPSMiningResult, String> {
-        // Generate blockchain seed based on block data and current state
-        let mut hasher = Sha256::new();
-        hasher.update(block_data.as_bytes());
-        hasher.update(self.blockchain_seed.to_be_bytes());
-        hasher.update(self.games_played.to_be_bytes());
-        let hash = hasher.finalize();
-        let block_seed = u64::from_be_bytes([
-            hash[0], hash[1], hash[2], hash[3],
-            hash[4], hash[5], hash[6], hash[7],
-        ]);
+    /// Mines with `strategy` instead of `SeededStrategy`, to study how it
+    /// changes mining times. Blocks mined this way don't verify.
+    #[allow(dead_code)]
+    pub fn set_strategy(&mut self, strategy: Arc<dyn PlayStrategy>) {
+        self.strategy = strategy;
+    }
+
+    pub fn mine_block(
+        &mut self,
+        block_data: &str,
+        mut progress: Option<ProgressCallback>,
+    ) -> Result<RPSMiningResult, MiningError> {
+        // Everything the games depend on comes from the block and the chain
+        // position, so any validator can replay them
+        let final_seed = Self::derive_final_seed(self.blockchain_seed, self.config.blocks_mined);
+        let block_seed = Self::derive_block_seed(block_data, final_seed);
+
+        // Players are derived from the block seed so the whole simulation
+        // can be reconstructed from the result alone
+        self.players = Self::create_players(&self.config.get_win_requirements(), block_seed, &self.strategy);
 
         let mut round = 0;
         let mut total_games = 0;
+        let mut tally = GameTally::default();
         let start_time = SystemTime::now();
+        let started = Instant::now();
 
         loop {
             round += 1;
-            let mut all_players_won = true;
-            let mut round_games = 0;
-
-            // Parallel simulation of games (in reality, you'd use CUDA here)
-            for player in &mut self.players {
-                if !player.has_won() {
-                    // Generate blockchain move for this round
-                    let blockchain_move_seed = block_seed
-                        .wrapping_add(round as u64)
-                        .wrapping_add(player.id as u64);
-                    let blockchain_move = Move::from_seed(blockchain_move_seed);
-
-                    // Player keeps playing until they win this round
-                    let mut player_won_round = false;
-                    while !player_won_round {
-                        let result = player.play_game(blockchain_move);
-                        round_games += 1;
-                        
-                        if result == GameResult::PlayerWin {
-                            player_won_round = true;
-                        }
-                        // If tie or blockchain wins, player plays again
-                    }
 
-                    if !player.has_won() {
-                        all_players_won = false;
-                    }
-                }
+            let game_budget = self.config.max_total_games.saturating_sub(total_games);
+            let round_tally = if self.config.parallel {
+                Self::play_round_parallel(&mut self.players, block_seed, round, game_budget)
+            } else {
+                Self::play_round(&mut self.players, block_seed, round, game_budget)
+            };
+            let round_games = round_tally.games();
+            tally += round_tally;
+            let players_remaining = self.players.iter().filter(|player| !player.has_won()).count();
+            let all_players_won = players_remaining == 0;
+
+            if let Some(callback) = progress.as_mut() {
+                callback(round, players_remaining);
             }
 
             total_games += round_games;
-            self.games_played += round_games as u64;
+            self.games_played += round_games;
+
+            if total_games > self.config.max_total_games {
+                return Err(MiningError::GameBudgetExceeded {
+                    rounds: round,
+                    total_games,
+                    budget: self.config.max_total_games,
+                });
+            }
 
             if all_players_won {
                 let mining_time = SystemTime::now()
@@ -227,49 +492,201 @@ impl RPSMiner {
                     rounds: round,
                     total_games,
                     mining_time_ms: mining_time,
-                    winning_players: self.players.clone(),
-                    final_seed: block_seed,
+                    winning_player_count: self.players.len() as u32,
+                    total_required_wins: self.players.iter().map(|player| player.required_wins).sum(),
+                    final_seed,
+                    player_wins: tally.player_wins,
+                    blockchain_wins: tally.blockchain_wins,
+                    ties: tally.ties,
                 };
 
-                // Reset players for next block and update config
-                for player in &mut self.players {
-                    player.reset();
-                }
                 self.config.blocks_mined += 1;
-                
-                // Update win requirements for next block
-                let new_requirements = self.config.get_win_requirements();
-                for (i, &required_wins) in new_requirements.iter().enumerate() {
-                    if let Some(player) = self.players.get_mut(i) {
-                        player.required_wins = required_wins;
-                    }
-                }
 
                 return Ok(result);
             }
 
             // Safety check to prevent infinite loops
-            if round > 1000000 {
-                return Err("Mining timeout: too many rounds".to_string());
+            if round >= self.config.max_rounds {
+                return Err(MiningError::RoundLimitExceeded {
+                    rounds: round,
+                    total_games,
+                });
+            }
+            if let Some(limit) = self.config.max_mining_time {
+                let elapsed = started.elapsed();
+                if elapsed >= limit {
+                    return Err(MiningError::TimedOut {
+                        rounds: round,
+                        total_games,
+                        elapsed_ms: elapsed.as_millis(),
+                        limit_ms: limit.as_millis(),
+                    });
+                }
             }
         }
     }
 
-    pub fn get_difficulty_info(&self) -> DifficultyInfo {
-        let requirements = self.config.get_win_requirements();
-        let total_required_wins: u32 = requirements.iter().sum();
-        
-        let mut win_distribution = HashMap::new();
-        for &wins in &requirements {
-            *win_distribution.entry(wins).or_insert(0) += 1;
+    /// Replays the games for `block_data` at this miner's current difficulty,
+    /// i.e. before the block being checked was mined
+    #[allow(dead_code)]
+    pub fn verify_block(&self, block_data: &str, result: &RPSMiningResult) -> bool {
+        Self::verify_result(block_data, result, &self.config, self.blockchain_seed)
+    }
+
+    /// Replays the games described by `result` and checks that every player
+    /// reaches their required wins in exactly the claimed rounds and games,
+    /// with the claimed outcomes. Results from before outcomes were recorded
+    /// carry all zeros and skip that part. `config` must describe the
+    /// difficulty the block was mined at.
+    pub fn verify_result(
+        block_data: &str,
+        result: &RPSMiningResult,
+        config: &RPSMiningConfig,
+        blockchain_seed: u64,
+    ) -> bool {
+        if !result.success {
+            return false;
         }
 
-        DifficultyInfo {
-            block_number: self.config.blocks_mined,
-            total_required_wins,
-            win_distribution,
-            total_players: self.config.total_players,
+        // The seed isn't the miner's choice, so it can't be ground for easy games
+        if result.final_seed != Self::derive_final_seed(blockchain_seed, config.blocks_mined) {
+            return false;
+        }
+
+        let win_requirements = config.get_win_requirements();
+        if result.winning_player_count as usize != win_requirements.len()
+            || result.total_required_wins != win_requirements.iter().sum::<u32>()
+        {
+            return false;
+        }
+
+        let block_seed = Self::derive_block_seed(block_data, result.final_seed);
+        let mut players = Self::create_players(&win_requirements, block_seed, &default_strategy());
+        let mut tally = GameTally::default();
+
+        for round in 1..=result.rounds {
+            let game_budget = result.total_games - tally.games();
+            tally += Self::play_round(&mut players, block_seed, round, game_budget);
+            if tally.games() > result.total_games {
+                return false;
+            }
+
+            if players.iter().all(|player| player.has_won()) {
+                let claimed = GameTally {
+                    player_wins: result.player_wins,
+                    blockchain_wins: result.blockchain_wins,
+                    ties: result.ties,
+                };
+                let outcomes_match = claimed == GameTally::default() || claimed == tally;
+                return round == result.rounds && tally.games() == result.total_games && outcomes_match;
+            }
+        }
+
+        false
+    }
+
+    fn derive_final_seed(blockchain_seed: u64, blocks_mined: u32) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(blockchain_seed.to_be_bytes());
+        hasher.update(blocks_mined.to_be_bytes());
+        seed_from_hash(&hasher.finalize())
+    }
+
+    fn derive_block_seed(block_data: &str, final_seed: u64) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(block_data.as_bytes());
+        hasher.update(final_seed.to_be_bytes());
+        seed_from_hash(&hasher.finalize())
+    }
+
+    fn create_players(win_requirements: &[u32], base_seed: u64, strategy: &Arc<dyn PlayStrategy>) -> Vec<Player> {
+        win_requirements
+            .iter()
+            .enumerate()
+            .map(|(i, &required_wins)| Player::new(i as u32, required_wins, base_seed).with_strategy(strategy.clone()))
+            .collect()
+    }
+
+    /// Plays one round for every player that still needs wins, returning the
+    /// outcomes of the games played. Stops early once more than `game_budget`
+    /// games have been played.
+    fn play_round(players: &mut [Player], block_seed: u64, round: u32, game_budget: u64) -> GameTally {
+        let mut tally = GameTally::default();
+
+        for player in players.iter_mut() {
+            if !player.has_won() {
+                // Generate blockchain move for this round
+                let mut hasher = Sha256::new();
+                hasher.update(block_seed.to_be_bytes());
+                hasher.update(round.to_be_bytes());
+                hasher.update(player.id.to_be_bytes());
+                let blockchain_move = Move::from_hash(&hasher.finalize());
+
+                // Player keeps playing until they win this round
+                let mut player_won_round = false;
+                while !player_won_round {
+                    if tally.games() > game_budget {
+                        return tally;
+                    }
+
+                    let result = player.play_game(blockchain_move, round, block_seed);
+                    tally.record(result);
+
+                    if result == GameResult::PlayerWin {
+                        player_won_round = true;
+                    }
+                    // If tie or blockchain wins, player plays again
+                }
+            }
         }
+
+        tally
+    }
+
+    /// Same as `play_round`, but with the players split across the available
+    /// cores when there are enough of them to be worth a thread each; see
+    /// `MIN_PLAYERS_PER_THREAD`. Otherwise the round is played in place.
+    fn play_round_parallel(players: &mut [Player], block_seed: u64, round: u32, game_budget: u64) -> GameTally {
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let threads = cores.min(players.len() / MIN_PLAYERS_PER_THREAD);
+        if threads <= 1 {
+            return Self::play_round(players, block_seed, round, game_budget);
+        }
+        Self::play_round_in_chunks(players, players.len().div_ceil(threads), block_seed, round, game_budget)
+    }
+
+    /// Plays the round with each `chunk_size` players on a thread of its own.
+    /// Each player's games only depend on the round's block seed and the
+    /// player's own state, so the outcome is identical to the sequential
+    /// path. Each chunk is held to the full `game_budget`, so the round can
+    /// overshoot it by at most a factor of the thread count.
+    fn play_round_in_chunks(
+        players: &mut [Player],
+        chunk_size: usize,
+        block_seed: u64,
+        round: u32,
+        game_budget: u64,
+    ) -> GameTally {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = players
+                .chunks_mut(chunk_size)
+                .map(|chunk| scope.spawn(move || Self::play_round(chunk, block_seed, round, game_budget)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("player simulation thread panicked"))
+                .fold(GameTally::default(), |mut total, tally| {
+                    total += tally;
+                    total
+                })
+        })
+    }
+
+    pub fn get_difficulty_info(&self) -> DifficultyInfo {
+        self.config.difficulty_info()
     }
 }
 
@@ -279,10 +696,53 @@ pub struct RPSMiningResult {
     pub rounds: u32,
     pub total_games: u64,
     pub mining_time_ms: u128,
-    pub winning_players: Vec<Player>,
+    pub winning_player_count: u32,
+    pub total_required_wins: u32,
     pub final_seed: u64,
+    /// Games the players won; with `blockchain_wins` and `ties` these add
+    /// up to `total_games`
+    #[serde(default)]
+    pub player_wins: u64,
+    #[serde(default)]
+    pub blockchain_wins: u64,
+    #[serde(default)]
+    pub ties: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiningError {
+    /// Players were still short of their required wins after `rounds` rounds
+    RoundLimitExceeded { rounds: u32, total_games: u64 },
+    /// More than `budget` games were played on the block
+    GameBudgetExceeded { rounds: u32, total_games: u64, budget: u64 },
+    /// Mining ran for longer than the configured `max_mining_time`
+    TimedOut { rounds: u32, total_games: u64, elapsed_ms: u128, limit_ms: u128 },
+}
+
+impl fmt::Display for MiningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiningError::RoundLimitExceeded { rounds, total_games } => write!(
+                f,
+                "Mining timeout: too many rounds ({} rounds, {} games played)",
+                rounds, total_games
+            ),
+            MiningError::GameBudgetExceeded { rounds, total_games, budget } => write!(
+                f,
+                "Mining aborted: game budget of {} exceeded ({} games in {} rounds)",
+                budget, total_games, rounds
+            ),
+            MiningError::TimedOut { rounds, total_games, elapsed_ms, limit_ms } => write!(
+                f,
+                "Mining timeout: time limit of {}ms exceeded after {}ms ({} rounds, {} games played)",
+                limit_ms, elapsed_ms, rounds, total_games
+            ),
+        }
+    }
 }
 
+impl std::error::Error for MiningError {}
+
 #[derive(Debug, Clone)]
 pub struct DifficultyInfo {
     #[allow(dead_code)]
@@ -308,15 +768,41 @@ impl DifficultyInfo {
 mod tests {
     use super::*;
 
+    fn move_for_seed(seed: u64) -> Move {
+        Move::from_hash(&Sha256::digest(seed.to_be_bytes()))
+    }
+
     #[test]
     fn test_move_generation() {
-        let move1 = Move::from_seed(0);
-        let move2 = Move::from_seed(1);
-        let move3 = Move::from_seed(2);
-        
-        assert_eq!(move1, Move::Rock);
-        assert_eq!(move2, Move::Paper);
-        assert_eq!(move3, Move::Scissors);
+        // The same seed always gives the same move
+        for seed in [0, 1, 2, u64::MAX] {
+            assert_eq!(move_for_seed(seed), move_for_seed(seed));
+        }
+
+        // Consecutive seeds don't cycle Rock, Paper, Scissors
+        let moves: Vec<Move> = (0..30).map(move_for_seed).collect();
+        let cycle: Vec<Move> = (0..30).map(|i| Move::from_index(i % 3)).collect();
+        assert_ne!(moves, cycle);
+
+        // 255s are rejected rather than reduced, and a digest of nothing
+        // else is hashed again
+        assert_eq!(Move::from_hash(&[255, 255, 4, 0]), Move::Paper);
+        assert_eq!(Move::from_hash(&[255; 32]), Move::from_hash(&Sha256::digest([255; 32])));
+    }
+
+    #[test]
+    fn test_seeded_moves_are_uniform() {
+        let seeds = 30_000u64;
+        let mut counts = [0u32; 3];
+        for seed in 0..seeds {
+            counts[move_for_seed(seed) as usize] += 1;
+        }
+
+        // Each move within 3% of a third, several standard deviations out
+        let expected = seeds as f64 / 3.0;
+        for count in counts {
+            assert!((count as f64 - expected).abs() < expected * 0.03, "{:?}", counts);
+        }
     }
 
     #[test]
@@ -327,6 +813,31 @@ mod tests {
         assert_eq!(Move::Rock.beats(&Move::Rock), GameResult::Tie);
     }
 
+    #[test]
+    fn test_move_distribution_is_uniform() {
+        let mut player = Player::new(0, u32::MAX, 99);
+        let games = 30_000;
+        let mut counts = [0u32; 3];
+
+        for game in 0..games {
+            let round = game / 10 + 1;
+            match player.next_move(round, 12345) {
+                Move::Rock => counts[0] += 1,
+                Move::Paper => counts[1] += 1,
+                Move::Scissors => counts[2] += 1,
+            }
+            player.games_played += 1;
+        }
+
+        // Chi-square with 2 degrees of freedom; 13.82 is the p = 0.001 cutoff
+        let expected = games as f64 / 3.0;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|&observed| (observed as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_square < 13.82, "counts {:?}, chi-square {}", counts, chi_square);
+    }
+
     #[test]
     fn test_player_creation() {
         let player = Player::new(0, 2, 12345);
@@ -343,6 +854,30 @@ mod tests {
         assert!(requirements.iter().all(|&x| x == 1));
     }
 
+    #[test]
+    fn test_custom_player_count() {
+        let mut config = RPSMiningConfig::new();
+        config.total_players = 10;
+        assert_eq!(config.get_win_requirements().len(), 10);
+
+        config.blocks_mined = 3;
+        let requirements = config.get_win_requirements();
+        assert_eq!(requirements.len(), 10);
+        assert_eq!(requirements.iter().filter(|&&x| x == 2).count(), 3);
+
+        // Past the player count every player needs extra wins
+        config.blocks_mined = 25;
+        let requirements = config.get_win_requirements();
+        assert_eq!(requirements.len(), 10);
+        assert!(requirements.iter().all(|&x| x >= 2));
+
+        let mut miner = RPSMiner::new(config);
+        assert_eq!(miner.players.len(), 10);
+        let result = miner.mine_block("small network", None).unwrap();
+        assert_eq!(miner.players.len(), 10);
+        assert!(result.total_games >= 20);
+    }
+
     #[test]
     fn test_difficulty_progression() {
         let mut config = RPSMiningConfig::new();
@@ -364,6 +899,93 @@ mod tests {
         assert_eq!(req3.iter().filter(|&&x| x == 2).count(), 2);
     }
 
+    #[test]
+    fn test_configurable_difficulty() {
+        // A step of 0 never ratchets, which keeps mining quick in tests
+        let mut flat = RPSMiningConfig { difficulty_step: 0, ..RPSMiningConfig::new() };
+        flat.blocks_mined = 50;
+        assert!(flat.get_win_requirements().iter().all(|&x| x == 1));
+        let mut miner = RPSMiner::new(flat);
+        let result = miner.mine_block("block", None).unwrap();
+        assert_eq!(result.total_required_wins, 100);
+
+        let mut steep = RPSMiningConfig {
+            base_required_wins: 3,
+            difficulty_step: 2,
+            ..RPSMiningConfig::with_players(4)
+        };
+        assert_eq!(steep.get_win_requirements(), [3, 3, 3, 3]);
+        steep.blocks_mined = 2;
+        assert_eq!(steep.get_win_requirements(), [3, 3, 5, 5]);
+
+        // Configs saved before these settings existed keep the old curve
+        let mut json = serde_json::to_value(RPSMiningConfig::new()).unwrap();
+        json.as_object_mut().unwrap().remove("base_required_wins");
+        json.as_object_mut().unwrap().remove("difficulty_step");
+        let mut legacy: RPSMiningConfig = serde_json::from_value(json).unwrap();
+        legacy.blocks_mined = 1;
+        assert_eq!(legacy.get_win_requirements().iter().filter(|&&x| x == 2).count(), 1);
+    }
+
+    #[test]
+    fn test_difficulty_adjustment() {
+        let mut miner = RPSMiner::new(RPSMiningConfig::new());
+        miner.config.blocks_mined = 10;
+        let initial_score = miner.get_difficulty_info().difficulty_score();
+
+        // Blocks arriving well under the 10s target raise the difficulty
+        let mut previous_score = initial_score;
+        for actual_ms in [2_000, 1_500, 3_000, 500] {
+            miner.config.adjust_for_block_time(actual_ms, 10_000);
+            let score = miner.get_difficulty_info().difficulty_score();
+            assert!(score > previous_score);
+            previous_score = score;
+        }
+
+        // Slow blocks bring it back down, but never below the base level
+        for _ in 0..20 {
+            miner.config.adjust_for_block_time(60_000, 10_000);
+        }
+        let lowered_score = miner.get_difficulty_info().difficulty_score();
+        assert!(lowered_score < previous_score);
+        assert!(lowered_score >= 1.0);
+
+        // Once the window is on target the difficulty holds steady
+        for _ in 0..miner.config.retarget_window {
+            miner.config.adjust_for_block_time(10_000, 10_000);
+        }
+        let adjustment = miner.config.difficulty_adjustment;
+        miner.config.adjust_for_block_time(10_000, 10_000);
+        assert_eq!(miner.config.difficulty_adjustment, adjustment);
+    }
+
+    #[test]
+    fn test_retarget_averages_recent_blocks() {
+        let mut config = RPSMiningConfig::new();
+        config.blocks_mined = 10;
+        for _ in 0..config.retarget_window {
+            config.retarget(10_000, 10_000);
+        }
+        assert_eq!(config.difficulty_adjustment, 0);
+
+        // A single fast block among on-target ones is smoothed out...
+        let initial_wins: u32 = config.get_win_requirements().iter().sum();
+        config.retarget(1_000, 10_000);
+        assert_eq!(config.difficulty_adjustment, 0);
+
+        // ...but a sustained run of them raises the requirements
+        config.retarget(1_000, 10_000);
+        assert_eq!(config.difficulty_adjustment, 1);
+        assert!(config.get_win_requirements().iter().sum::<u32>() > initial_wins);
+        assert_eq!(config.recent_block_times_ms.len(), config.retarget_window);
+
+        // And a run of slow ones lowers them again
+        for _ in 0..config.retarget_window {
+            config.retarget(40_000, 10_000);
+        }
+        assert!(config.get_win_requirements().iter().sum::<u32>() < initial_wins);
+    }
+
     #[test]
     fn test_miner_creation() {
         let config = RPSMiningConfig::new();
@@ -371,4 +993,300 @@ mod tests {
         assert_eq!(miner.players.len(), 100);
         assert!(miner.players.iter().all(|p| p.required_wins == 1));
     }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        // Enough players for two threads, so a machine with the cores for it
+        // takes the threaded path
+        let parallel_config = RPSMiningConfig::with_players(2 * MIN_PLAYERS_PER_THREAD as u32);
+        let sequential_config = RPSMiningConfig { parallel: false, ..parallel_config.clone() };
+        // Same seed on both sides so only the execution strategy differs
+        let mut sequential = RPSMiner::with_seed(sequential_config, 42);
+        let mut parallel = RPSMiner::with_seed(parallel_config, 42);
+
+        for block in 0..3 {
+            let block_data = format!("block-{}", block);
+            let seq_result = sequential.mine_block(&block_data, None).unwrap();
+            let par_result = parallel.mine_block(&block_data, None).unwrap();
+
+            assert_eq!(seq_result.rounds, par_result.rounds);
+            assert_eq!(seq_result.total_games, par_result.total_games);
+            assert_eq!(seq_result.final_seed, par_result.final_seed);
+            assert_eq!(
+                (seq_result.player_wins, seq_result.blockchain_wins, seq_result.ties),
+                (par_result.player_wins, par_result.blockchain_wins, par_result.ties)
+            );
+        }
+        assert_eq!(sequential.games_played, parallel.games_played);
+    }
+
+    #[test]
+    fn test_seeded_mining_is_reproducible() {
+        let mut first = RPSMiner::with_seed(RPSMiningConfig::new(), 7);
+        let mut second = RPSMiner::with_seed(RPSMiningConfig::new(), 7);
+
+        for block in 0..3 {
+            let block_data = format!("seeded-{}", block);
+            let a = first.mine_block(&block_data, None).unwrap();
+            let b = second.mine_block(&block_data, None).unwrap();
+
+            assert_eq!(a.rounds, b.rounds);
+            assert_eq!(a.total_games, b.total_games);
+            assert_eq!(a.final_seed, b.final_seed);
+        }
+
+        let mut other = RPSMiner::with_seed(RPSMiningConfig::new(), 8);
+        let c = other.mine_block("seeded-0", None).unwrap();
+        let mut fresh = RPSMiner::with_seed(RPSMiningConfig::new(), 7);
+        assert_ne!(c.final_seed, fresh.mine_block("seeded-0", None).unwrap().final_seed);
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        let mut config = RPSMiningConfig::new();
+        config.blocks_mined = 50;
+        let mut miner = RPSMiner::with_seed(config, 3);
+
+        let mut updates = Vec::new();
+        let mut record = |round: u32, players_remaining: usize| updates.push((round, players_remaining));
+        let result = miner.mine_block("progress", Some(&mut record)).unwrap();
+
+        assert_eq!(updates.len(), result.rounds as usize);
+        for (i, window) in updates.windows(2).enumerate() {
+            assert_eq!(window[0].0, i as u32 + 1);
+            assert!(window[1].1 <= window[0].1);
+        }
+        assert_eq!(updates.last().unwrap().1, 0);
+    }
+
+    #[test]
+    fn test_round_limit() {
+        let mut config = RPSMiningConfig::new();
+        // Some players need two wins, so mining needs at least two rounds
+        config.blocks_mined = 10;
+        config.max_rounds = 1;
+        let mut miner = RPSMiner::new(config);
+
+        match miner.mine_block("capped", None) {
+            Err(MiningError::RoundLimitExceeded { rounds, total_games }) => {
+                assert_eq!(rounds, 1);
+                assert!(total_games >= 100);
+            }
+            other => panic!("expected round limit error, got {:?}", other),
+        }
+        assert_eq!(miner.config.blocks_mined, 10);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let mut config = RPSMiningConfig::new();
+        // Some players need two wins, so the first round can't finish the block
+        config.blocks_mined = 10;
+        config.max_mining_time = Some(Duration::ZERO);
+        let mut miner = RPSMiner::new(config);
+
+        match miner.mine_block("timed", None) {
+            Err(e @ MiningError::TimedOut { rounds: 1, limit_ms: 0, .. }) => {
+                assert!(e.to_string().contains("time limit"));
+            }
+            other => panic!("expected time limit error, got {:?}", other),
+        }
+        assert_eq!(miner.config.blocks_mined, 10);
+
+        // Without a limit the same block mines fine
+        miner.config.max_mining_time = None;
+        assert!(miner.mine_block("timed", None).is_ok());
+    }
+
+    #[test]
+    fn test_game_budget() {
+        let mut config = RPSMiningConfig::new();
+        config.max_total_games = 10;
+        let mut miner = RPSMiner::new(config);
+
+        match miner.mine_block("budget", None) {
+            Err(MiningError::GameBudgetExceeded { rounds, total_games, budget }) => {
+                assert_eq!(rounds, 1);
+                assert_eq!(budget, 10);
+                assert!(total_games > 10);
+            }
+            other => panic!("expected game budget error, got {:?}", other),
+        }
+        assert_eq!(miner.config.blocks_mined, 0);
+    }
+
+    #[test]
+    fn test_verify_result() {
+        let mut miner = RPSMiner::new(RPSMiningConfig::new());
+        let config = miner.config.clone();
+        let result = miner.mine_block("block data", None).unwrap();
+
+        assert!(RPSMiner::verify_result("block data", &result, &config, 0));
+
+        let mut tampered = result.clone();
+        tampered.total_games -= 1;
+        assert!(!RPSMiner::verify_result("block data", &tampered, &config, 0));
+
+        let mut tampered = result.clone();
+        tampered.rounds += 1;
+        assert!(!RPSMiner::verify_result("block data", &tampered, &config, 0));
+
+        let mut tampered = result.clone();
+        tampered.final_seed += 1;
+        assert!(!RPSMiner::verify_result("block data", &tampered, &config, 0));
+
+        // Claimed outcomes must match the replay, unless they predate being recorded
+        let mut tampered = result.clone();
+        tampered.ties -= 1;
+        tampered.player_wins += 1;
+        assert!(!RPSMiner::verify_result("block data", &tampered, &config, 0));
+
+        let mut legacy = result.clone();
+        (legacy.player_wins, legacy.blockchain_wins, legacy.ties) = (0, 0, 0);
+        assert!(RPSMiner::verify_result("block data", &legacy, &config, 0));
+    }
+
+    #[test]
+    fn test_outcome_counts() {
+        let mut miner = RPSMiner::new(RPSMiningConfig::new());
+        let result = miner.mine_block("block data", None).unwrap();
+
+        assert_eq!(result.player_wins + result.blockchain_wins + result.ties, result.total_games);
+        // Every required win is a player win, and the mine stops once they're all in
+        assert_eq!(result.player_wins, result.total_required_wins as u64);
+        assert!(result.blockchain_wins > 0 && result.ties > 0);
+    }
+
+    #[test]
+    fn test_mining_is_deterministic_across_nodes() {
+        let mut config = RPSMiningConfig::new();
+        config.blocks_mined = 5;
+
+        let mut miner = RPSMiner::new(config.clone());
+        let mut other_node = RPSMiner::new(config.clone());
+        let validator = RPSMiner::new(config);
+
+        let result = miner.mine_block("prev:abc merkle:def", None).unwrap();
+        let other_result = other_node.mine_block("prev:abc merkle:def", None).unwrap();
+        assert_eq!(result.rounds, other_result.rounds);
+        assert_eq!(result.total_games, other_result.total_games);
+        assert_eq!(result.final_seed, other_result.final_seed);
+
+        assert!(validator.verify_block("prev:abc merkle:def", &result));
+
+        let mut tampered = result.clone();
+        tampered.total_games += 1;
+        assert!(!validator.verify_block("prev:abc merkle:def", &tampered));
+    }
+
+    #[test]
+    fn test_configured_player_count() {
+        for total_players in [1, 7, 100, 250] {
+            let mut config = RPSMiningConfig::with_players(total_players);
+            let miner = RPSMiner::new(config.clone());
+            assert_eq!(miner.players.len(), total_players as usize);
+
+            // Extra wins spread over the configured players, not a fixed 100
+            config.blocks_mined = total_players + 3;
+            let requirements = config.get_win_requirements();
+            assert_eq!(requirements.len(), total_players as usize);
+            assert!(requirements.iter().all(|&wins| wins >= 2));
+        }
+
+        let mut miner = RPSMiner::new(RPSMiningConfig::with_players(5));
+        let result = miner.mine_block("small network", None).unwrap();
+        assert_eq!(result.winning_player_count, 5);
+        assert_eq!(miner.players.len(), 5);
+
+        assert_eq!(RPSMiningConfig::with_players(0).total_players, 1);
+    }
+
+    #[test]
+    fn test_play_strategies() {
+        // Average games per win for one player against a blockchain move
+        // that changes every round, as in mining
+        let games_per_win = |strategy: Arc<dyn PlayStrategy>| {
+            let mut player = Player::new(0, u32::MAX, 7).with_strategy(strategy);
+            for round in 0..2_000 {
+                let blockchain_move = move_for_seed(round as u64);
+                while player.play_game(blockchain_move, round, 42) != GameResult::PlayerWin {}
+            }
+            player.games_played as f64 / player.current_wins as f64
+        };
+
+        // The seeded walk wins a third of its games
+        let seeded = games_per_win(Arc::new(SeededStrategy));
+        assert!((2.7..3.3).contains(&seeded), "{}", seeded);
+
+        // Countering the last move wins every replay within a round
+        let countering = games_per_win(Arc::new(CounterStrategy { bias_percent: 100 }));
+        assert!(countering < 2.0, "{}", countering);
+        let unbiased = games_per_win(Arc::new(CounterStrategy { bias_percent: 0 }));
+        assert_eq!(unbiased, seeded);
+
+        // A miner can be given a strategy, but only seeded blocks verify
+        let config = RPSMiningConfig { parallel: false, ..RPSMiningConfig::with_players(20) };
+        let mut miner = RPSMiner::new(config.clone());
+        let seeded_result = miner.mine_block("block", None).unwrap();
+        let mut miner = RPSMiner::new(config.clone());
+        miner.set_strategy(Arc::new(CounterStrategy { bias_percent: 100 }));
+        let countered_result = miner.mine_block("block", None).unwrap();
+        assert!(countered_result.total_games < seeded_result.total_games);
+        assert!(RPSMiner::verify_result("block", &seeded_result, &config, 0));
+        assert!(!RPSMiner::verify_result("block", &countered_result, &config, 0));
+    }
+
+    #[test]
+    fn test_chunked_round_matches_sequential() {
+        let requirements = RPSMiningConfig::with_players(50).get_win_requirements();
+        let strategy = default_strategy();
+        let mut sequential = RPSMiner::create_players(&requirements, 9, &strategy);
+        let mut chunked = RPSMiner::create_players(&requirements, 9, &strategy);
+
+        for round in 1..=3 {
+            // Uneven chunks, so the last thread gets fewer players
+            let expected = RPSMiner::play_round(&mut sequential, 9, round, u64::MAX);
+            let actual = RPSMiner::play_round_in_chunks(&mut chunked, 7, 9, round, u64::MAX);
+            assert_eq!(actual, expected);
+        }
+        for (a, b) in sequential.iter().zip(&chunked) {
+            assert_eq!((a.current_wins, a.games_played), (b.current_wins, b.games_played));
+        }
+    }
+
+    /// What `MIN_PLAYERS_PER_THREAD` is based on. Run with
+    /// `cargo test --release bench_round_threading -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_round_threading() {
+        let spawns: u32 = 2_000;
+        let start = Instant::now();
+        for _ in 0..spawns {
+            std::thread::scope(|scope| scope.spawn(|| ()).join().unwrap());
+        }
+        println!("spawn and join: {:?}", start.elapsed() / spawns);
+
+        let strategy = default_strategy();
+        for players in [100, 1_000, 10_000] {
+            let requirements = RPSMiningConfig::with_players(players).get_win_requirements();
+            let mut sequential_time = Duration::ZERO;
+            let mut threaded_time = Duration::ZERO;
+            for block_seed in 0..10 {
+                let mut sequential = RPSMiner::create_players(&requirements, block_seed, &strategy);
+                let mut threaded = sequential.clone();
+                let start = Instant::now();
+                RPSMiner::play_round(&mut sequential, block_seed, 1, u64::MAX);
+                sequential_time += start.elapsed();
+                let start = Instant::now();
+                RPSMiner::play_round_in_chunks(&mut threaded, (players as usize).div_ceil(4), block_seed, 1, u64::MAX);
+                threaded_time += start.elapsed();
+            }
+            println!(
+                "{} players: sequential {:?}, four threads {:?} per round",
+                players,
+                sequential_time / 10,
+                threaded_time / 10
+            );
+        }
+    }
 }
@@ -1,10 +1,25 @@
 use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 
+/// Something that happened while a block was executed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    Transfer { from: String, to: String, amount: u128 },
+    Reward { to: String, amount: u128 },
+    BlockMined { index: u32, games: u64 },
+    /// A pending transaction the miner left out of the block. Only the
+    /// mining node records these; a replay of the chain never sees them.
+    TransactionSkipped { from: String, nonce: u32, reason: String },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pallet {
     block_number: u32,
-    nonce: BTreeMap<String, u32>
+    nonce: BTreeMap<String, u32>,
+    #[serde(default)]
+    blocks_mined: BTreeMap<String, u32>, // Blocks each miner has mined
+    #[serde(default)]
+    events: BTreeMap<u32, Vec<Event>> // Events deposited by each block, keyed by block number
 }
 
 impl Pallet{
@@ -14,7 +29,9 @@ impl Pallet{
         Self {
 
             block_number: 0,
-            nonce: BTreeMap::new()
+            nonce: BTreeMap::new(),
+            blocks_mined: BTreeMap::new(),
+            events: BTreeMap::new()
 
         }
     } 
@@ -25,9 +42,24 @@ impl Pallet{
         self.block_number
     }
 
-    pub fn inc_block_number(&mut self, _who: &String){
+    pub fn inc_block_number(&mut self, who: &String){
 
         self.block_number = self.block_number.checked_add(1).unwrap(); // Fails only @ blockchain overflow
+        let mined: u32 = self.blocks_mined_by(who);
+        self.blocks_mined.insert(who.clone(), mined + 1);
+    }
+
+    pub fn dec_block_number(&mut self, who: &String){
+
+        self.events.remove(&self.block_number); // The undone block's events go with it
+        self.block_number = self.block_number.saturating_sub(1);
+        let mined: u32 = self.blocks_mined_by(who).saturating_sub(1);
+        self.blocks_mined.insert(who.clone(), mined);
+    }
+
+    pub fn blocks_mined_by(&self, who: &String) -> u32 {
+
+        *self.blocks_mined.get(who).unwrap_or(&0)
     }
 
     pub fn inc_nonce(&mut self, who: &String){
@@ -36,9 +68,27 @@ impl Pallet{
         self.nonce.insert(who.clone(), nonce + 1);
     }
 
+    pub fn dec_nonce(&mut self, who: &String){
+
+        let nonce: u32 = self.get_nonce(who).saturating_sub(1);
+        self.nonce.insert(who.clone(), nonce);
+    }
+
     pub fn get_nonce(&self, who: &String) -> u32 {
 
         *self.nonce.get(who).unwrap_or(&0)
     }
 
+    /// Records an event against the block being executed, the one after the current block number
+    pub fn deposit_event(&mut self, event: Event){
+
+        self.events.entry(self.block_number + 1).or_default().push(event);
+    }
+
+    #[allow(dead_code)]
+    pub fn get_events(&self, block_number: u32) -> &[Event] {
+
+        self.events.get(&block_number).map(Vec::as_slice).unwrap_or(&[])
+    }
+
 }
\ No newline at end of file
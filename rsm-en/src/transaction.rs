@@ -1,31 +1,63 @@
+use crate::blockchain::NETWORK_ACCOUNT;
 use crate::merkle::Hash;
 use crate::rps_mining::{RPSMiningResult};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Most bytes of application data a transaction may carry
+pub const MAX_DATA_LEN: usize = 256;
+
+/// What a transaction does, which decides how it's checked and applied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    /// Moves funds between accounts, checked against the sender's balance and nonce
+    #[default]
+    Transfer,
+    /// Mints the block reward and fees to the miner, from the network account
+    Coinbase,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Transaction {
+    #[serde(default)]
+    pub kind: TransactionKind,
     pub from: String,
     pub to: String,
     pub amount: u128,
+    /// Paid by the sender on top of `amount`, collected by the block's miner
+    #[serde(default)]
+    pub fee: u128,
     pub nonce: u32,
     pub timestamp: u64,
+    /// Unix time after which the transaction may no longer be mined
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+    /// Memo or application payload, up to `MAX_DATA_LEN` bytes. Hashed like
+    /// every other field, and sent over the wire as hex.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "hex_data")]
+    pub data: Vec<u8>,
     pub hash: Hash,
 }
 
 impl Transaction {
     pub fn new(from: String, to: String, amount: u128, nonce: u32) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        Self::new_with_fee(from, to, amount, 0, nonce)
+    }
+
+    pub fn new_with_fee(from: String, to: String, amount: u128, fee: u128, nonce: u32) -> Self {
+        let timestamp = current_timestamp();
         let mut tx = Self {
+            kind: TransactionKind::Transfer,
             from,
             to,
             amount,
+            fee,
             nonce,
             timestamp,
-            hash: Hash::from_string(""), // Temporary
+            valid_until: None,
+            data: Vec::new(),
+            hash: Hash::zero(), // Temporary
         };
         
         // Calculate the actual hash
@@ -33,24 +65,208 @@ impl Transaction {
         tx
     }
 
+    /// The reward transaction paying `amount` to a block's miner. It mints
+    /// rather than transfers, so there's no balance or nonce to check.
+    pub fn coinbase(to: String, amount: u128) -> Self {
+        let mut tx = Self::new(NETWORK_ACCOUNT.to_string(), to, amount, 0);
+        tx.kind = TransactionKind::Coinbase;
+        tx.hash = tx.calculate_hash();
+        tx
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.kind == TransactionKind::Coinbase
+    }
+
+    /// Sets an expiry time, after which the transaction is dropped from the
+    /// mempool instead of being mined
+    #[allow(dead_code)]
+    pub fn with_valid_until(mut self, valid_until: u64) -> Self {
+        self.valid_until = Some(valid_until);
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    /// Attaches a memo or application payload
+    #[allow(dead_code)]
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self.hash = self.calculate_hash();
+        self
+    }
+
     pub fn calculate_hash(&self) -> Hash {
-        let data = format!(
-            "{}{}{}{}{}",
-            self.from, self.to, self.amount, self.nonce, self.timestamp
-        );
-        Hash::from_string(&data)
+        Hash::from_bytes(&self.canonical_bytes())
+    }
+
+    /// The bytes the hash commits to. Strings are length prefixed and numbers
+    /// fixed width, so no two transactions share an encoding.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut encoder = CanonicalEncoder::default();
+        encoder.u8(match self.kind {
+            TransactionKind::Transfer => 0,
+            TransactionKind::Coinbase => 1,
+        });
+        encoder.str(&self.from);
+        encoder.str(&self.to);
+        encoder.u128(self.amount);
+        encoder.u128(self.fee);
+        encoder.u32(self.nonce);
+        encoder.u64(self.timestamp);
+        match self.valid_until {
+            Some(valid_until) => {
+                encoder.u8(1);
+                encoder.u64(valid_until);
+            }
+            None => encoder.u8(0),
+        }
+        encoder.bytes(&self.data);
+        encoder.finish()
     }
 
+    /// Well formed and not expired, i.e. still fit to be mined now
     pub fn is_valid(&self) -> bool {
+        self.is_well_formed() && !self.is_expired_at(current_timestamp())
+    }
+
+    /// Checks the transaction's contents alone. Transactions already in a
+    /// block are held to this, since expiry only matters until they're mined.
+    /// Coinbases, and only coinbases, come from the network account.
+    pub fn is_well_formed(&self) -> bool {
         self.hash == self.calculate_hash() && 
         !self.from.is_empty() && 
         !self.to.is_empty() &&
-        self.from != self.to
+        self.from != self.to &&
+        self.is_coinbase() == (self.from == NETWORK_ACCOUNT) &&
+        self.data.len() <= MAX_DATA_LEN
+    }
+
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        self.valid_until.is_some_and(|valid_until| valid_until < now)
+    }
+}
+
+/// Builds the unambiguous byte layout that transaction and block hashes are
+/// taken over: big-endian fixed-width integers, length-prefixed strings
+#[derive(Default)]
+struct CanonicalEncoder {
+    bytes: Vec<u8>,
+}
+
+impl CanonicalEncoder {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
     }
 
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u128(&mut self, value: u128) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.u64(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    fn hash(&mut self, value: &Hash) {
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+// Transaction data goes over the wire as a hex string, like hashes do
+mod hex_data {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex_string = String::deserialize(deserializer)?;
+        hex::decode(hex_string).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Current Unix time in seconds
+pub fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// What a block's header records of the RPS games that mined it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RPSSummary {
+    pub rounds: u32,
+    pub total_games: u64,
+}
+
+/// Everything the block hash commits to, without the transactions themselves,
+/// which the merkle root stands in for. Light clients sync these alone.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlockHeader {
+    pub index: u32,
+    pub timestamp: u64,
+    pub previous_hash: Hash,
+    pub merkle_root: Hash,
+    pub state_root: Hash,
+    pub hash: Hash,
+    pub rps_summary: Option<RPSSummary>,
+}
+
+impl BlockHeader {
+    pub fn calculate_hash(&self) -> Hash {
+        Hash::from_bytes(&self.canonical_bytes())
+    }
+
+    /// The header fields the block hash commits to, in the same encoding as
+    /// `Transaction::canonical_bytes`
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut encoder = CanonicalEncoder::default();
+        encoder.u32(self.index);
+        encoder.u64(self.timestamp);
+        encoder.hash(&self.previous_hash);
+        encoder.hash(&self.merkle_root);
+        encoder.hash(&self.state_root);
+        match self.rps_summary {
+            Some(ref summary) => {
+                encoder.u8(1);
+                encoder.u32(summary.rounds);
+                encoder.u64(summary.total_games);
+            }
+            None => encoder.u8(0), // Not mined yet
+        }
+        encoder.finish()
+    }
+
+    /// Whether this header's hash is its own and it follows on from `previous`
     #[allow(dead_code)]
-    pub fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap_or_default()
+    pub fn links_to(&self, previous: &BlockHeader) -> bool {
+        self.hash == self.calculate_hash()
+            && self.previous_hash == previous.hash
+            && self.index == previous.index + 1
     }
 }
 
@@ -61,16 +277,15 @@ pub struct Block {
     pub transactions: Vec<Transaction>,
     pub previous_hash: Hash,
     pub merkle_root: Hash,
+    /// Root of the account state after this block's transactions are applied
+    pub state_root: Hash,
     pub hash: Hash,
     pub rps_mining_result: Option<RPSMiningResult>,
 }
 
 impl Block {
-    pub fn new(index: u32, transactions: Vec<Transaction>, previous_hash: Hash) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    pub fn new(index: u32, transactions: Vec<Transaction>, previous_hash: Hash, state_root: Hash) -> Self {
+        let timestamp = current_timestamp();
         let merkle_root = Self::calculate_merkle_root(&transactions);
         
         let mut block = Self {
@@ -79,7 +294,8 @@ impl Block {
             transactions,
             previous_hash,
             merkle_root,
-            hash: Hash::from_string(""), // Temporary
+            state_root,
+            hash: Hash::zero(), // Temporary
             rps_mining_result: None,
         };
         
@@ -87,32 +303,39 @@ impl Block {
         block
     }
 
-    pub fn genesis() -> Self {
-        let genesis_hash = Hash::from_string("genesis");
-        Self::new(0, Vec::new(), genesis_hash)
+    /// Whether `Blockchain::prune` has dropped this block's transactions. Every
+    /// mined block has a coinbase, so a merkle root over nothing gives it away.
+    pub fn is_pruned(&self) -> bool {
+        self.index > 0 && self.transactions.is_empty() && !self.merkle_root.is_zero()
+    }
+
+    /// The genesis block has no previous block, so its previous hash is zero
+    pub fn genesis(state_root: Hash) -> Self {
+        Self::new(0, Vec::new(), Hash::zero(), state_root)
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp,
+            previous_hash: self.previous_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            state_root: self.state_root.clone(),
+            hash: self.hash.clone(),
+            rps_summary: self.rps_mining_result.as_ref().map(|result| RPSSummary {
+                rounds: result.rounds,
+                total_games: result.total_games,
+            }),
+        }
     }
 
     pub fn calculate_hash(&self) -> Hash {
-        let rps_data = if let Some(ref result) = self.rps_mining_result {
-            format!("{}:{}", result.rounds, result.total_games)
-        } else {
-            "pending".to_string()
-        };
-        
-        let data = format!(
-            "{}{}{}{}{}",
-            self.index,
-            self.timestamp,
-            self.previous_hash.to_hex(),
-            self.merkle_root.to_hex(),
-            rps_data
-        );
-        Hash::from_string(&data)
+        self.header().calculate_hash()
     }
 
-    fn calculate_merkle_root(transactions: &[Transaction]) -> Hash {
+    pub fn calculate_merkle_root(transactions: &[Transaction]) -> Hash {
         if transactions.is_empty() {
-            return Hash::from_string("empty");
+            return Hash::zero();
         }
 
         let mut tree = crate::merkle::FastMerkleTree::new();
@@ -121,21 +344,28 @@ impl Block {
         }
         tree.build();
         
-        tree.get_root().cloned().unwrap_or_else(|| Hash::from_string("empty"))
+        tree.get_root().cloned().unwrap_or_else(Hash::zero)
     }
 
-    pub fn mine_block_rps(&mut self, rps_miner: &mut crate::rps_mining::RPSMiner) -> Result<(), String> {
-        // Create block data for RPS mining
-        let block_data = format!(
-            "{}{}{}{}",
-            self.index,
-            self.timestamp,
-            self.previous_hash.to_hex(),
-            self.merkle_root.to_hex()
-        );
-        
+    /// The block contents the RPS games are bound to, hex encoded from the
+    /// same layout as `BlockHeader::canonical_bytes` so no two blocks share it
+    pub fn rps_block_data(&self) -> String {
+        let mut encoder = CanonicalEncoder::default();
+        encoder.u32(self.index);
+        encoder.u64(self.timestamp);
+        encoder.hash(&self.previous_hash);
+        encoder.hash(&self.merkle_root);
+        encoder.hash(&self.state_root);
+        hex::encode(encoder.finish())
+    }
+
+    pub fn mine_block_rps(
+        &mut self,
+        rps_miner: &mut crate::rps_mining::RPSMiner,
+        progress: Option<crate::rps_mining::ProgressCallback>,
+    ) -> Result<(), crate::rps_mining::MiningError> {
         // Use RPS mining to mine the block
-        match rps_miner.mine_block(&block_data) {
+        match rps_miner.mine_block(&self.rps_block_data(), progress) {
             Ok(mining_result) => {
                 self.rps_mining_result = Some(mining_result.clone());
                 self.hash = self.calculate_hash();
@@ -200,7 +430,7 @@ impl Block {
 
         // Check if all transactions are valid
         for tx in &self.transactions {
-            if !tx.is_valid() {
+            if !tx.is_well_formed() {
                 return false;
             }
         }
@@ -225,19 +455,14 @@ impl Block {
 
     #[allow(dead_code)]
     pub fn verify_transaction_inclusion(&self, tx: &Transaction, proof: &[Hash], tx_index: usize) -> bool {
-        let mut tree = crate::merkle::FastMerkleTree::new();
-        for transaction in &self.transactions {
-            tree.add_leaf(transaction.hash.clone());
-        }
-        tree.build();
-        
-        tree.verify_proof(&tx.hash, proof, tx_index)
+        crate::merkle::verify_proof_against_root(&tx.hash, proof, tx_index, &self.merkle_root)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rps_mining::{RPSMiner, RPSMiningConfig};
 
     #[test]
     fn test_transaction_creation() {
@@ -259,21 +484,21 @@ mod tests {
             1
         );
         let previous_hash = Hash::from_string("previous");
-        let block = Block::new(1, vec![tx], previous_hash);
+        let block = Block::new(1, vec![tx], previous_hash, Hash::from_string("state"));
         assert_eq!(block.index, 1);
         assert_eq!(block.transactions.len(), 1);
     }
 
     #[test]
     fn test_genesis_block() {
-        let genesis = Block::genesis();
+        let genesis = Block::genesis(Hash::from_string("state"));
         assert_eq!(genesis.index, 0);
         assert!(genesis.transactions.is_empty());
     }
 
     #[test]
     fn test_block_validation() {
-        let genesis = Block::genesis();
+        let genesis = Block::genesis(Hash::from_string("state"));
         assert!(genesis.is_valid(None));
 
         let tx = Transaction::new(
@@ -282,7 +507,148 @@ mod tests {
             100,
             1
         );
-        let block = Block::new(1, vec![tx], genesis.hash.clone());
+        let block = Block::new(1, vec![tx], genesis.hash.clone(), Hash::from_string("state"));
         assert!(block.is_valid(Some(&genesis)));
     }
+
+    #[test]
+    fn test_mined_block_does_not_embed_players() {
+        let tx = Transaction::new(
+            "alice".to_string(),
+            "bob".to_string(),
+            100,
+            1
+        );
+        let mut miner = RPSMiner::new(RPSMiningConfig::new());
+        let mut block = Block::new(1, vec![tx], Hash::from_string("previous"), Hash::from_string("state"));
+        block.mine_block_rps(&mut miner, None).unwrap();
+
+        let rps_result = block.rps_mining_result.as_ref().unwrap();
+        assert_eq!(rps_result.winning_player_count, 100);
+        assert_eq!(rps_result.total_required_wins, 100);
+
+        // The whole block should serialize smaller than the player list alone
+        let block_json = serde_json::to_string(&block).unwrap();
+        let players_json = serde_json::to_string(&miner.players).unwrap();
+        assert!(block_json.len() * 2 < players_json.len());
+    }
+
+    #[test]
+    fn test_zero_hash_markers() {
+        assert!(Hash::zero().is_zero());
+        assert!(!Hash::from_string("").is_zero());
+
+        let genesis = Block::genesis(Hash::from_string("state"));
+        assert!(genesis.previous_hash.is_zero());
+        assert!(genesis.merkle_root.is_zero());
+        assert!(Block::calculate_merkle_root(&[]).is_zero());
+
+        let tx = Transaction::new("alice".to_string(), "bob".to_string(), 1, 1);
+        assert!(!Block::calculate_merkle_root(&[tx]).is_zero());
+    }
+
+    #[test]
+    fn test_hash_preimage_is_unambiguous() {
+        let mut first = Transaction::new("ab".to_string(), "c".to_string(), 1, 1);
+        let mut second = Transaction::new("a".to_string(), "bc".to_string(), 1, 1);
+        second.timestamp = first.timestamp;
+        second.hash = second.calculate_hash();
+
+        // Run together the way the old format! preimage did, these are identical
+        let naive = |tx: &Transaction| format!("{}{}{}{}{}{}", tx.from, tx.to, tx.amount, tx.fee, tx.nonce, tx.timestamp);
+        assert_eq!(naive(&first), naive(&second));
+        assert_ne!(first.canonical_bytes(), second.canonical_bytes());
+        assert_ne!(first.hash, second.hash);
+
+        // Digits can't run into each other either
+        first = Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 12, 1, 1);
+        second = Transaction::new_with_fee("alice".to_string(), "bob".to_string(), 1, 21, 1);
+        second.timestamp = first.timestamp;
+        assert_ne!(first.calculate_hash(), second.calculate_hash());
+
+        // Nor can a block's index and timestamp in the data its games are bound to
+        let mut first = Block::new(1, vec![], Hash::zero(), Hash::zero());
+        let mut second = first.clone();
+        first.timestamp = 23;
+        second.index = 12;
+        second.timestamp = 3;
+        assert_ne!(first.rps_block_data(), second.rps_block_data());
+    }
+
+    #[test]
+    fn test_transaction_data() {
+        let plain = Transaction::new("alice".to_string(), "bob".to_string(), 10, 1);
+        let memo = plain.clone().with_data(b"rent for march".to_vec());
+        assert!(memo.is_valid());
+        assert_ne!(memo.hash, plain.hash);
+        let other_memo = plain.clone().with_data(b"rent for april".to_vec());
+        assert_ne!(other_memo.hash, memo.hash);
+
+        // Editing the data after signing off on the hash is caught
+        let mut tampered = memo.clone();
+        tampered.data = b"rent for april".to_vec();
+        assert!(!tampered.is_valid());
+
+        assert!(plain.clone().with_data(vec![0; MAX_DATA_LEN]).is_valid());
+        let oversize = plain.clone().with_data(vec![0; MAX_DATA_LEN + 1]);
+        assert!(!oversize.is_valid());
+        assert!(!oversize.is_well_formed());
+
+        // Hex on the wire, and left out entirely when there's none
+        let json = serde_json::to_value(&memo).unwrap();
+        assert_eq!(json["data"], hex::encode(b"rent for march"));
+        assert_eq!(serde_json::from_value::<Transaction>(json).unwrap(), memo);
+        let json = serde_json::to_value(&plain).unwrap();
+        assert!(json.get("data").is_none());
+        assert_eq!(serde_json::from_value::<Transaction>(json).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_block_header() {
+        let genesis = Block::genesis(Hash::from_string("state"));
+        let tx = Transaction::new("alice".to_string(), "bob".to_string(), 100, 1);
+        let mut block = Block::new(1, vec![tx], genesis.hash.clone(), Hash::from_string("state"));
+        block.mine_block_rps(&mut RPSMiner::new(RPSMiningConfig::new()), None).unwrap();
+
+        let header = block.header();
+        assert_eq!(header.hash, block.hash);
+        assert_eq!(header.calculate_hash(), block.hash);
+        assert_eq!(header.merkle_root, block.merkle_root);
+        let summary = header.rps_summary.as_ref().unwrap();
+        assert_eq!(summary.total_games, block.rps_mining_result.as_ref().unwrap().total_games);
+        assert!(header.links_to(&genesis.header()));
+
+        // A header carrying someone else's hash doesn't link
+        let mut forged = header.clone();
+        forged.timestamp += 1;
+        assert!(!forged.links_to(&genesis.header()));
+        assert!(genesis.header().rps_summary.is_none());
+    }
+
+    #[test]
+    fn test_transaction_kinds() {
+        let coinbase = Transaction::coinbase("miner".to_string(), 100);
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.from, NETWORK_ACCOUNT);
+        assert!(coinbase.is_valid());
+
+        // A transfer from the network account isn't allowed...
+        let transfer = Transaction::new(NETWORK_ACCOUNT.to_string(), "miner".to_string(), 100, 0);
+        assert_eq!(transfer.kind, TransactionKind::Transfer);
+        assert!(!transfer.is_valid());
+        assert_ne!(transfer.hash, coinbase.hash);
+
+        // ...nor a coinbase from anyone else
+        let mut forged = Transaction::new("alice".to_string(), "miner".to_string(), 100, 0);
+        forged.kind = TransactionKind::Coinbase;
+        forged.hash = forged.calculate_hash();
+        assert!(!forged.is_valid());
+
+        // Transactions saved without a kind are transfers
+        let mut json = serde_json::to_value(Transaction::new("alice".to_string(), "bob".to_string(), 1, 1)).unwrap();
+        json.as_object_mut().unwrap().remove("kind");
+        let legacy: Transaction = serde_json::from_value(json).unwrap();
+        assert_eq!(legacy.kind, TransactionKind::Transfer);
+        assert!(legacy.is_valid());
+    }
 }
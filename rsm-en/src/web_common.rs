@@ -0,0 +1,554 @@
+// Types and logic behind the web server in web_main.rs that don't depend on
+// how requests arrive: sessions, API responses, paging and live updates
+
+use crate::blockchain::{Blockchain, BlockchainError};
+use crate::merkle::Hash;
+use crate::transaction::{Block, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinerSession {
+    pub id: String,
+    pub name: String,
+    pub total_phlopcoin: f64,
+    pub blocks_mined: u32,
+    pub mining_history: Vec<MiningResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiningResult {
+    pub block_number: u32,
+    pub phlopcoin_earned: f64,
+    pub games_played: u64,
+    pub rounds: u32,
+    pub timestamp: String,
+    #[serde(default)]
+    pub block_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartMiningRequest {
+    pub miner_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MineBlockRequest {
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitTransactionRequest {
+    pub from: String,
+    pub to: String,
+    pub amount: u128,
+    pub nonce: u32,
+    #[serde(default)]
+    pub fee: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionResponse {
+    pub success: bool,
+    pub message: String,
+    pub tx_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProofResponse {
+    pub tx_hash: String,
+    pub tx_index: usize,
+    pub block_index: u32,
+    pub merkle_root: String,
+    pub proof: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceResponse {
+    pub address: String,
+    pub balance: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NonceResponse {
+    pub address: String,
+    /// Nonce of the account's last mined transaction
+    pub nonce: u32,
+    /// Nonce to put on the account's next transaction
+    pub next_nonce: u32,
+}
+
+/// What `/api/mempool` returns: the transactions waiting for the next block,
+/// plus how many more are queued behind a nonce gap
+#[derive(Debug, Serialize)]
+pub struct MempoolResponse {
+    pub count: usize,
+    pub queued_count: usize,
+    pub transactions: Vec<Transaction>,
+}
+
+/// `/api/state`: every balance, and the state root they hash to
+#[derive(Debug, Serialize)]
+pub struct StateResponse {
+    pub state_root: String,
+    pub balances: BTreeMap<String, u128>,
+}
+
+/// One row of `/api/leaderboard`
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntry {
+    pub address: String,
+    pub rewards: u128,
+}
+
+/// One point of `/api/difficulty-history`
+#[derive(Debug, Serialize)]
+pub struct DifficultyPoint {
+    pub block_index: u32,
+    pub difficulty_score: f64,
+}
+
+/// `?offset=&limit=` for the paged endpoints; missing values fall back to
+/// the start and `DEFAULT_PAGE_LIMIT`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct PageQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// One window of a longer list, with the list's full length so clients
+/// know how far they can page
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MiningResponse {
+    pub success: bool,
+    pub message: String,
+    pub session: Option<MinerSession>,
+    pub mining_result: Option<MiningResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockchainStatus {
+    pub total_blocks: usize,
+    pub total_games_played: u64,
+    pub current_difficulty_score: f64,
+    pub active_miners: usize,
+}
+
+/// Pushed to every `/ws` subscriber when the chain or a session changes
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LiveUpdate {
+    BlockMined { block_index: u32, status: BlockchainStatus },
+    SessionUpdated { session_id: String, status: BlockchainStatus },
+}
+
+/// The `/ws` subscribers, each fed JSON updates through its own channel. A
+/// subscriber whose receiver has been dropped, because its connection went
+/// away, is forgotten on the next publish.
+#[derive(Debug, Default)]
+pub struct LiveUpdates {
+    subscribers: Mutex<Vec<Sender<String>>>,
+}
+
+/// Where miner sessions are kept between restarts
+pub const SESSIONS_FILE: &str = "phlopchain_sessions.json";
+
+/// Where the blockchain is kept between restarts
+pub const CHAIN_FILE: &str = "phlopchain_chain.json";
+
+/// Address the server listens on unless `PHLOPCHAIN_ADDR` says otherwise
+pub const DEFAULT_BIND_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Port the server listens on unless `PHLOPCHAIN_PORT` says otherwise
+pub const DEFAULT_PORT: u16 = 3030;
+
+/// Origins that get CORS headers back unless `PHLOPCHAIN_ALLOWED_ORIGINS`
+/// says otherwise; browsers on any other origin can't read the responses
+pub const ALLOWED_ORIGINS: &[&str] = &["http://localhost:3030", "http://127.0.0.1:3030"];
+
+/// Items a paged endpoint returns when the request doesn't give a limit
+pub const DEFAULT_PAGE_LIMIT: usize = 20;
+
+/// Most items a paged endpoint returns, whatever limit is asked for
+pub const MAX_PAGE_LIMIT: usize = 100;
+
+impl PageQuery {
+    /// The offset and limit to serve, with the limit capped at
+    /// `MAX_PAGE_LIMIT`
+    pub fn window(&self) -> (usize, usize) {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        (self.offset.unwrap_or(0), limit)
+    }
+
+    /// The page of `items` this query asks for
+    pub fn page<T>(&self, items: impl ExactSizeIterator<Item = T>) -> Page<T> {
+        let (offset, limit) = self.window();
+        let total = items.len();
+        Page { total, offset, limit, items: items.skip(offset).take(limit).collect() }
+    }
+}
+
+impl LiveUpdates {
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner).push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, update: &LiveUpdate) {
+        let json = serde_json::to_string(update).unwrap_or_default();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|subscriber| subscriber.send(json.clone()).is_ok());
+    }
+
+    /// Ends every subscription, which closes their connections
+    pub fn disconnect_all(&self) {
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    #[allow(dead_code)]
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+}
+
+impl MinerSession {
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            total_phlopcoin: 0.0,
+            blocks_mined: 0,
+            mining_history: Vec::new(),
+        }
+    }
+}
+
+impl MiningResponse {
+    pub fn failure(message: String, session: Option<MinerSession>) -> Self {
+        Self {
+            success: false,
+            message,
+            session,
+            mining_result: None,
+        }
+    }
+}
+
+/// Resolves the listening address from the `PHLOPCHAIN_ADDR` and
+/// `PHLOPCHAIN_PORT` values, falling back to 127.0.0.1:3030 for either
+pub fn bind_address(addr: Option<String>, port: Option<String>) -> Result<SocketAddr, String> {
+    let ip = match addr {
+        Some(addr) => addr.parse().map_err(|_| format!("PHLOPCHAIN_ADDR '{}' is not an IP address", addr))?,
+        None => DEFAULT_BIND_ADDR,
+    };
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| format!("PHLOPCHAIN_PORT '{}' is not a port number", port))?,
+        None => DEFAULT_PORT,
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Resolves the CORS allowlist from a comma-separated
+/// `PHLOPCHAIN_ALLOWED_ORIGINS` value, falling back to `ALLOWED_ORIGINS`
+pub fn allowed_origins(origins: Option<String>) -> Vec<String> {
+    match origins {
+        Some(origins) => origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => ALLOWED_ORIGINS.iter().map(|origin| origin.to_string()).collect(),
+    }
+}
+
+/// Reads saved sessions, starting empty when the file is missing or unreadable
+pub fn load_sessions(path: &Path) -> HashMap<String, MinerSession> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            println!("Ignoring unreadable sessions file {}: {}", path.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Writes to a temporary file and renames it into place, so a crash mid-write
+/// never leaves a truncated file behind. Callers hold the sessions lock while
+/// saving, which keeps two saves from interleaving.
+pub fn save_sessions(path: &Path, sessions: &HashMap<String, MinerSession>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(sessions)?;
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, json)?;
+    fs::rename(&temp_path, path)
+}
+
+pub fn current_status(blockchain: &Blockchain, active_miners: usize) -> BlockchainStatus {
+    BlockchainStatus {
+        total_blocks: blockchain.get_chain_length(),
+        total_games_played: blockchain.get_total_rps_games(),
+        current_difficulty_score: blockchain.get_rps_difficulty_info().difficulty_score(),
+        active_miners,
+    }
+}
+
+/// HTTP status code and reason for a transaction the chain refused: 409 if
+/// it clashes with the sender's nonce sequence, 503 if there's no room for
+/// it right now, and 400 for anything wrong with the transaction itself
+pub fn rejection_status(error: &BlockchainError) -> (u16, &'static str) {
+    match error {
+        BlockchainError::InvalidNonce { .. } => (409, "CONFLICT"),
+        BlockchainError::QueueFull | BlockchainError::MempoolFull => (503, "SERVICE UNAVAILABLE"),
+        _ => (400, "BAD REQUEST"),
+    }
+}
+
+pub fn mempool_response(blockchain: &Blockchain) -> MempoolResponse {
+    MempoolResponse {
+        count: blockchain.get_pending_transaction_count(),
+        queued_count: blockchain.get_queued_transaction_count(),
+        transactions: blockchain.get_pending_transactions().iter().cloned().collect(),
+    }
+}
+
+pub fn state_response(blockchain: &Blockchain) -> StateResponse {
+    StateResponse {
+        state_root: blockchain.get_state_root().unwrap_or_else(Hash::zero).to_hex(),
+        balances: blockchain.get_all_balances(),
+    }
+}
+
+/// The difficulty score each block was mined at, oldest first
+pub fn difficulty_history_response(blockchain: &Blockchain) -> Vec<DifficultyPoint> {
+    blockchain
+        .get_difficulty_history()
+        .iter()
+        .map(|&(block_index, difficulty_score)| DifficultyPoint { block_index, difficulty_score })
+        .collect()
+}
+
+/// Reward totals per miner, as recorded on the chain itself
+pub fn leaderboard_response(blockchain: &Blockchain) -> Vec<LeaderboardEntry> {
+    blockchain
+        .get_miner_leaderboard()
+        .into_iter()
+        .map(|(address, rewards)| LeaderboardEntry { address, rewards })
+        .collect()
+}
+
+/// Node metrics in the Prometheus text exposition format
+pub fn render_metrics(blockchain: &Blockchain, active_miners: usize) -> String {
+    let chain_length = blockchain.get_chain_length();
+    let metrics: [(&str, &str, &str, String); 6] = [
+        ("phlopchain_chain_length", "gauge", "Blocks in the chain, genesis included", chain_length.to_string()),
+        ("phlopchain_blocks_mined_total", "counter", "Blocks mined since genesis", chain_length.saturating_sub(1).to_string()),
+        ("phlopchain_rps_games_total", "counter", "Rock-paper-scissors games played to mine the chain", blockchain.get_total_rps_games().to_string()),
+        ("phlopchain_difficulty_score", "gauge", "Current RPS mining difficulty score", blockchain.get_rps_difficulty_info().difficulty_score().to_string()),
+        ("phlopchain_pending_transactions", "gauge", "Transactions waiting in the mempool", blockchain.get_pending_transaction_count().to_string()),
+        ("phlopchain_active_miners", "gauge", "Mining sessions on this node", active_miners.to_string()),
+    ];
+
+    metrics
+        .iter()
+        .map(|(name, kind, help, value)| format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"))
+        .collect()
+}
+
+/// A page of every session's mining results, newest block first
+pub fn mining_history_page(sessions: &HashMap<String, MinerSession>, query: &PageQuery) -> Page<MiningResult> {
+    let mut history: Vec<&MiningResult> = sessions
+        .values()
+        .flat_map(|session| session.mining_history.iter())
+        .collect();
+    history.sort_by_key(|result| std::cmp::Reverse(result.block_number));
+    query.page(history.into_iter().cloned())
+}
+
+/// A page of the chain's blocks, genesis first
+pub fn blocks_page(blockchain: &Blockchain, query: &PageQuery) -> Page<Block> {
+    query.page(blockchain.blocks().cloned())
+}
+
+/// Mines a block for `session`, first queueing a couple of small demo
+/// transfers to and from the miner so the block has something in it, and
+/// credits the session with the block's PhlopCoin reward (n / a^2)
+pub fn mine_for_session(blockchain: &mut Blockchain, session: &mut MinerSession) -> Result<(Block, MiningResult), String> {
+    // Either may be refused, e.g. before the miner has earned anything to send
+    let to_miner = Transaction::new("alice".to_string(), session.name.clone(), 5, blockchain.get_next_nonce(&"alice".to_string()));
+    let from_miner = Transaction::new(session.name.clone(), "bob".to_string(), 3, blockchain.get_next_nonce(&session.name));
+    for tx in [to_miner, from_miner] {
+        if let Err(e) = blockchain.add_transaction(tx) {
+            println!("Skipping demo transaction: {}", e);
+        }
+    }
+
+    let block = blockchain.mine_pending_transactions(session.name.clone()).map_err(|e| e.to_string())?;
+    let rps_result = block.rps_mining_result.as_ref().ok_or("Block mined but no RPS result found")?;
+    let phlopcoin_earned = Blockchain::phlopcoin_reward(rps_result);
+
+    let mining_result = MiningResult {
+        block_number: block.index,
+        phlopcoin_earned,
+        games_played: rps_result.total_games,
+        rounds: rps_result.rounds,
+        timestamp: format_timestamp(SystemTime::now()),
+        block_hash: block.hash.to_hex(),
+    };
+
+    session.total_phlopcoin += phlopcoin_earned;
+    session.blocks_mined += 1;
+    session.mining_history.push(mining_result.clone());
+    Ok((block, mining_result))
+}
+
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+
+    // Simple timestamp formatting
+    format!("{} seconds since epoch", secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_address() {
+        assert_eq!(bind_address(None, None), Ok(SocketAddr::from(([127, 0, 0, 1], 3030))));
+        assert_eq!(
+            bind_address(Some("0.0.0.0".to_string()), Some("8080".to_string())),
+            Ok(SocketAddr::from(([0, 0, 0, 0], 8080)))
+        );
+        assert_eq!(bind_address(Some("::1".to_string()), None).unwrap().to_string(), "[::1]:3030");
+
+        assert!(bind_address(Some("localhost:80".to_string()), None).is_err());
+        assert!(bind_address(None, Some("70000".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_sessions_round_trip_through_file() {
+        let path = std::env::temp_dir().join(format!("phlopchain_web_common_sessions_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(load_sessions(&path).is_empty());
+
+        let mut session = MinerSession::new("session-1".to_string(), "persistent-miner".to_string());
+        session.total_phlopcoin = 1.5;
+        session.blocks_mined = 3;
+        let mut sessions = HashMap::new();
+        sessions.insert(session.id.clone(), session);
+        save_sessions(&path, &sessions).unwrap();
+
+        let restored = load_sessions(&path);
+        assert_eq!(restored["session-1"].name, "persistent-miner");
+        assert_eq!(restored["session-1"].blocks_mined, 3);
+
+        // A corrupt file starts the server with no sessions instead of failing
+        fs::write(&path, "{ not json").unwrap();
+        assert!(load_sessions(&path).is_empty());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_mine_for_session() {
+        let mut blockchain = Blockchain::new();
+        let mut session = MinerSession::new("session-1".to_string(), "carol".to_string());
+
+        let (block, result) = mine_for_session(&mut blockchain, &mut session).unwrap();
+        assert_eq!(result.block_number, block.index);
+        assert_eq!(result.block_hash, block.hash.to_hex());
+        assert_eq!(result.phlopcoin_earned, Blockchain::phlopcoin_reward(block.rps_mining_result.as_ref().unwrap()));
+        assert_eq!(session.blocks_mined, 1);
+        assert_eq!(session.total_phlopcoin, result.phlopcoin_earned);
+
+        // With a reward to spend, the miner's demo transfer goes through too
+        let (block, _) = mine_for_session(&mut blockchain, &mut session).unwrap();
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(session.mining_history.len(), 2);
+
+        let sessions: HashMap<String, MinerSession> = [(session.id.clone(), session)].into_iter().collect();
+        let history = mining_history_page(&sessions, &PageQuery { offset: None, limit: Some(1) });
+        assert_eq!(history.total, 2);
+        assert_eq!(history.items.len(), 1);
+        assert_eq!(history.items[0].block_number, 2);
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 1)).unwrap();
+
+        let metrics = render_metrics(&blockchain, 3);
+        assert!(metrics.contains("# HELP phlopchain_chain_length "));
+        assert!(metrics.contains("# TYPE phlopchain_chain_length gauge\nphlopchain_chain_length 2\n"));
+        assert!(metrics.contains("# TYPE phlopchain_blocks_mined_total counter\nphlopchain_blocks_mined_total 1\n"));
+        assert!(metrics.contains("\nphlopchain_pending_transactions 1\n"));
+        assert!(metrics.contains("\nphlopchain_active_miners 3\n"));
+        assert!(metrics.contains(&format!("\nphlopchain_rps_games_total {}\n", blockchain.get_total_rps_games())));
+
+        // Every sample line is preceded by its HELP and TYPE lines
+        let lines: Vec<&str> = metrics.lines().collect();
+        assert_eq!(lines.len(), 18);
+        for sample in lines.chunks(3) {
+            let name = sample[2].split(' ').next().unwrap();
+            assert!(sample[0].starts_with(&format!("# HELP {} ", name)));
+            assert!(sample[1].starts_with(&format!("# TYPE {} ", name)));
+        }
+    }
+
+    #[test]
+    fn test_rejection_status() {
+        assert_eq!(rejection_status(&BlockchainError::InvalidNonce { expected: 2, got: 1 }).0, 409);
+        assert_eq!(rejection_status(&BlockchainError::MempoolFull).0, 503);
+        assert_eq!(rejection_status(&BlockchainError::QueueFull).0, 503);
+        assert_eq!(rejection_status(&BlockchainError::InsufficientBalance { have: 0, need: 1 }).0, 400);
+        assert_eq!(rejection_status(&BlockchainError::InvalidTransaction).0, 400);
+    }
+
+    #[test]
+    fn test_live_updates_forget_dropped_subscribers() {
+        let updates = LiveUpdates::default();
+        let kept = updates.subscribe();
+        let dropped = updates.subscribe();
+        assert_eq!(updates.subscriber_count(), 2);
+
+        drop(dropped);
+        let status = current_status(&Blockchain::new(), 0);
+        updates.publish(&LiveUpdate::BlockMined { block_index: 1, status });
+        assert_eq!(updates.subscriber_count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&kept.try_recv().unwrap()).unwrap();
+        assert_eq!(json["event"], "block_mined");
+        assert_eq!(json["block_index"], 1);
+        assert_eq!(json["status"]["total_blocks"], 1);
+    }
+
+    #[test]
+    fn test_allowed_origins() {
+        assert_eq!(allowed_origins(None), ALLOWED_ORIGINS);
+        assert_eq!(
+            allowed_origins(Some("https://phlopchain.example, http://localhost:8080,".to_string())),
+            ["https://phlopchain.example", "http://localhost:8080"]
+        );
+        // Set but empty allows no origin at all
+        assert!(allowed_origins(Some(String::new())).is_empty());
+    }
+}
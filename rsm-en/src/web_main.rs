@@ -1,8 +1,12 @@
+use std::borrow::Cow;
 use std::io::prelude::*;
-use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::collections::HashMap;
-use std::thread;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 // Include the blockchain modules
 mod balances;
@@ -11,119 +15,495 @@ mod merkle;
 mod transaction;
 mod blockchain;
 mod rps_mining;
+mod web_common;
+mod websocket;
 
 use blockchain::Blockchain;
-use transaction::Transaction;
-use serde::{Deserialize, Serialize};
+use merkle::Hash;
+use transaction::{Block, Transaction};
+use web_common::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MinerSession {
-    id: String,
-    name: String,
-    total_phlopcoin: f64,
-    blocks_mined: u32,
-    mining_history: Vec<MiningResult>,
+type SharedBlockchain = Arc<Mutex<Blockchain>>;
+type SharedSessions = Arc<Mutex<HashMap<String, MinerSession>>>;
+
+/// What every connection shares
+#[derive(Clone)]
+struct ServerState {
+    blockchain: SharedBlockchain,
+    sessions: SharedSessions,
+    updates: Arc<LiveUpdates>,
+    /// Saved after every mined block
+    chain_file: PathBuf,
+    /// Saved whenever a session starts or mines
+    sessions_file: PathBuf,
+    /// Origins whose requests get CORS headers back
+    allowed_origins: Vec<String>,
+    /// Set by `shutdown()` to stop `serve`
+    shutdown: Arc<AtomicBool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MiningResult {
-    block_number: u32,
-    phlopcoin_earned: f64,
-    games_played: u64,
-    rounds: u32,
-    timestamp: String,
-    block_hash: String, // Add block hash
+// Set from the SIGINT and SIGTERM handlers; stops every `serve` loop
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+// How long the accept loop sleeps when no connection is waiting
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// How long a shutdown waits for in-flight requests before saving anyway
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+impl ServerState {
+    /// Restores the chain and sessions saved in these files, if any
+    fn load(chain_file: impl Into<PathBuf>, sessions_file: impl Into<PathBuf>) -> Self {
+        let chain_file = chain_file.into();
+        let sessions_file = sessions_file.into();
+        Self {
+            blockchain: Arc::new(Mutex::new(load_blockchain(&chain_file))),
+            sessions: Arc::new(Mutex::new(load_sessions(&sessions_file))),
+            updates: Arc::new(LiveUpdates::default()),
+            chain_file,
+            sessions_file,
+            allowed_origins: allowed_origins(None),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stops `serve` from accepting connections, after which it lets the
+    /// in-flight ones finish and saves
+    #[allow(dead_code)]
+    fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    fn shutdown_requested(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst) || SIGNALLED.load(Ordering::SeqCst)
+    }
+
+    /// Writes the chain and sessions to their files
+    fn save(&self) {
+        let blockchain_guard = self.blockchain.lock().unwrap_or_else(PoisonError::into_inner);
+        match blockchain_guard.save_to_file(&self.chain_file) {
+            Ok(()) => println!("💾 Saved {} blocks to {}", blockchain_guard.get_chain_length(), self.chain_file.display()),
+            Err(e) => println!("Failed to save blockchain: {}", e),
+        }
+        drop(blockchain_guard);
+
+        let sessions_guard = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        match save_sessions(&self.sessions_file, &sessions_guard) {
+            Ok(()) => println!("💾 Saved {} sessions to {}", sessions_guard.len(), self.sessions_file.display()),
+            Err(e) => println!("Failed to save sessions: {}", e),
+        }
+    }
+
+    /// Replaces the origins that get CORS headers back
+    fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = origins;
+        self
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct StartMiningRequest {
-    miner_name: String,
+fn main() {
+    let bound = bind_address(std::env::var("PHLOPCHAIN_ADDR").ok(), std::env::var("PHLOPCHAIN_PORT").ok())
+        .and_then(|addr| bind_listener(addr).map(|listener| (addr, listener)));
+    let (addr, listener) = match bound {
+        Ok(bound) => bound,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let state = ServerState::load(CHAIN_FILE, SESSIONS_FILE)
+        .with_allowed_origins(allowed_origins(std::env::var("PHLOPCHAIN_ALLOWED_ORIGINS").ok()));
+
+    println!("🌐 PhlopChain web server running on http://{}", addr);
+    println!("📖 Visit http://{} in your browser to start mining!", addr);
+
+    #[cfg(unix)]
+    install_signal_handlers();
+    if let Err(e) = serve(listener, &state) {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct MineBlockRequest {
-    session_id: String,
+/// Accepts connections until `shutdown()` or a SIGINT or SIGTERM, then
+/// closes the live update connections, gives in-flight requests up to
+/// `SHUTDOWN_GRACE_PERIOD` to finish, and saves the chain and sessions
+fn serve(listener: TcpListener, state: &ServerState) -> std::io::Result<()> {
+    // Non-blocking, so the loop gets to check for a shutdown between connections
+    listener.set_nonblocking(true)?;
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+
+    // A failed connection is logged and skipped; each one is handled on its
+    // own thread, so a panicking handler can't stop the accept loop either
+    while !state.shutdown_requested() {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            }
+            Err(e) => {
+                println!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nonblocking(false) {
+            println!("Failed to accept connection: {}", e);
+            continue;
+        }
+
+        let connection_state = state.clone();
+        let spawned = thread::Builder::new().spawn(move || {
+            handle_connection(stream, &connection_state);
+        });
+        match spawned {
+            Ok(connection) => connections.push(connection),
+            Err(e) => println!("Failed to start a connection thread: {}", e),
+        }
+        connections.retain(|connection| !connection.is_finished());
+    }
+
+    println!("🛑 Shutting down...");
+    state.updates.disconnect_all();
+    let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+    while connections.iter().any(|connection| !connection.is_finished()) && Instant::now() < deadline {
+        thread::sleep(ACCEPT_POLL_INTERVAL);
+    }
+    state.save();
+    Ok(())
 }
 
-#[derive(Debug, Serialize)]
-struct MiningResponse {
-    success: bool,
-    message: String,
-    session: Option<MinerSession>,
-    mining_result: Option<MiningResult>,
+/// Turns SIGINT (Ctrl+C) and SIGTERM into a graceful shutdown
+#[cfg(unix)]
+fn install_signal_handlers() {
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    // Only touches an atomic, which is safe to do from a signal handler
+    extern "C" fn request_shutdown(_signum: i32) {
+        SIGNALLED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: `signal` is libc's, which std already links, and the handler
+    // is async-signal-safe
+    unsafe {
+        signal(SIGINT, request_shutdown);
+        signal(SIGTERM, request_shutdown);
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct BlockchainStatus {
-    total_blocks: usize,
-    total_games_played: u64,
-    current_difficulty_score: f64,
-    active_miners: usize,
+fn bind_listener(addr: SocketAddr) -> Result<TcpListener, String> {
+    TcpListener::bind(addr).map_err(|e| format!("Could not bind to {}: {}", addr, e))
 }
 
-type SharedBlockchain = Arc<Mutex<Blockchain>>;
-type SharedSessions = Arc<Mutex<HashMap<String, MinerSession>>>;
+/// Loads the saved chain, starting from a fresh genesis block when there is
+/// no saved chain or it fails validation
+fn load_blockchain(path: &Path) -> Blockchain {
+    if !path.exists() {
+        return Blockchain::new();
+    }
+    match Blockchain::load_from_file(path) {
+        Ok(blockchain) => {
+            println!("📂 Loaded {} blocks from {}", blockchain.get_chain_length(), path.display());
+            blockchain
+        }
+        Err(e) => {
+            println!("Starting a new chain: {}", e);
+            Blockchain::new()
+        }
+    }
+}
 
-fn main() {
-    println!("🌐 PhlopChain Web Interface starting on http://localhost:3030");
-    println!("📖 Visit http://localhost:3030 in your browser to start mining!");
-    
-    let blockchain = Arc::new(Mutex::new(Blockchain::new()));
-    let sessions: SharedSessions = Arc::new(Mutex::new(HashMap::new()));
+// Upper bound on the request line and headers, before any body
+const MAX_HEADER_BYTES: usize = 64 * 1024;
 
-    let listener = TcpListener::bind("0.0.0.0:3030").unwrap();
-    println!("PhlopChain web server running on http://0.0.0.0:3030");
-    
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-        let blockchain_clone = Arc::clone(&blockchain);
-        let sessions_clone = Arc::clone(&sessions);
-        
-        thread::spawn(move || {
-            handle_connection(stream, blockchain_clone, sessions_clone);
-        });
+// Upper bound on a request body, answered with 413 when exceeded
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Why a request couldn't be read off the connection
+#[derive(Debug, PartialEq)]
+enum RequestError {
+    Malformed(&'static str),
+    BodyTooLarge,
+}
+
+impl RequestError {
+    fn status_line(&self) -> &'static str {
+        match self {
+            RequestError::Malformed(_) => "HTTP/1.1 400 BAD REQUEST",
+            RequestError::BodyTooLarge => "HTTP/1.1 413 PAYLOAD TOO LARGE",
+        }
     }
 }
 
-fn handle_connection(mut stream: TcpStream, blockchain: SharedBlockchain, sessions: SharedSessions) {
-    let mut buffer = [0; 4096]; // Increased buffer size
-    let bytes_read = stream.read(&mut buffer).unwrap_or(0);
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Malformed(reason) => write!(f, "{}", reason),
+            RequestError::BodyTooLarge => write!(f, "Request body larger than {} bytes", MAX_BODY_BYTES),
+        }
+    }
+}
+
+// Response bodies are written out this many bytes at a time
+const RESPONSE_CHUNK_SIZE: usize = 8 * 1024;
+
+fn handle_connection(mut stream: TcpStream, state: &ServerState) {
+    let request = read_request(&mut stream);
+
+    // A websocket upgrade keeps the connection open for live updates
+    if let Some(key) = request.as_deref().ok().and_then(websocket_key) {
+        if let Err(e) = websocket::serve_updates(stream, key, state.updates.subscribe()) {
+            println!("Live update connection failed: {}", e);
+        }
+        return;
+    }
+
+    let (status_line, contents, origin) = match request {
+        Ok(request) => {
+            let origin = allowed_origin(&request, &state.allowed_origins).map(str::to_string);
+            let (status_line, contents) = route_request(&request, state);
+            (status_line, contents, origin)
+        }
+        Err(e) => (e.status_line().to_string(), Cow::Owned(e.to_string()), None),
+    };
     
-    let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+    if let Err(e) = write_response(&mut stream, &status_line, &contents, origin.as_deref()) {
+        println!("Failed to send response: {}", e);
+    }
+}
+
+/// Value of the request's `name` header, matched case-insensitively
+fn header_value<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .split("\r\n\r\n")
+        .next()?
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// The request's Origin header, if it is one of `allowed_origins`
+fn allowed_origin<'a>(request: &'a str, allowed_origins: &[String]) -> Option<&'a str> {
+    header_value(request, "origin").filter(|origin| allowed_origins.iter().any(|allowed| allowed == origin))
+}
+
+/// The client's `Sec-WebSocket-Key`, if this is a request to upgrade `/ws`
+/// to a websocket
+fn websocket_key(request: &str) -> Option<&str> {
+    let (method, segments) = parse_request_line(request.lines().next().unwrap_or(""));
+    let upgrading = header_value(request, "upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    if method != "GET" || segments != ["ws"] || !upgrading {
+        return None;
+    }
+    header_value(request, "sec-websocket-key")
+}
+
+/// Writes the headers, then streams the body in `RESPONSE_CHUNK_SIZE` pieces
+/// rather than building the whole response in memory first. CORS headers are
+/// only sent when `origin` is an allowed origin to echo back.
+fn write_response<W: Write>(writer: &mut W, status_line: &str, contents: &str, origin: Option<&str>) -> std::io::Result<()> {
+    let cors_headers = match origin {
+        Some(origin) => format!(
+            "Access-Control-Allow-Origin: {}\r\nVary: Origin\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n",
+            origin
+        ),
+        None => String::new(),
+    };
+    let headers = format!(
+        "{}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}\r\n",
+        status_line,
+        if contents.starts_with("{") || contents.starts_with("[") {
+            "application/json"
+        } else if contents.starts_with("# ") {
+            // Prometheus text format, from /metrics
+            "text/plain; version=0.0.4"
+        } else {
+            "text/html"
+        },
+        contents.len(),
+        cors_headers
+    );
+    writer.write_all(headers.as_bytes())?;
+
+    for chunk in contents.as_bytes().chunks(RESPONSE_CHUNK_SIZE) {
+        writer.write_all(chunk)?;
+    }
+    writer.flush()
+}
+
+/// Reads a whole HTTP request: everything up to the blank line after the
+/// headers, then exactly `Content-Length` bytes of body, however many reads
+/// that takes. Bodies over `MAX_BODY_BYTES` are refused before being read.
+fn read_request<R: Read>(reader: &mut R) -> Result<String, RequestError> {
+    let mut data = Vec::new();
+    let mut chunk = [0; 4096];
+
+    let header_end = loop {
+        if let Some(pos) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if data.len() > MAX_HEADER_BYTES {
+            return Err(RequestError::Malformed("Request headers too large"));
+        }
+
+        let bytes_read = reader.read(&mut chunk).map_err(|_| RequestError::Malformed("Failed to read request"))?;
+        if bytes_read == 0 {
+            // Connection closed before the headers ended; route what we have
+            return Ok(String::from_utf8_lossy(&data).to_string());
+        }
+        data.extend_from_slice(&chunk[..bytes_read]);
+    };
+
+    let headers = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut content_length = 0;
+    for line in headers.lines().skip(1) {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| RequestError::Malformed("Malformed Content-Length header"))?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(RequestError::BodyTooLarge);
+    }
+
+    while data.len() < header_end + content_length {
+        let bytes_read = reader.read(&mut chunk).map_err(|_| RequestError::Malformed("Failed to read request"))?;
+        if bytes_read == 0 {
+            return Err(RequestError::Malformed("Request body shorter than Content-Length"));
+        }
+        data.extend_from_slice(&chunk[..bytes_read]);
+    }
+    data.truncate(header_end + content_length);
+
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
+
+fn route_request(request: &str, state: &ServerState) -> (String, Cow<'static, str>) {
+    let blockchain = Arc::clone(&state.blockchain);
+    let sessions = Arc::clone(&state.sessions);
     let request_line = request.lines().next().unwrap_or("");
     
     println!("Received request: {}", request_line); // Debug log
     
-    let (status_line, contents) = if request_line.starts_with("GET / ") {
-        println!("📄 Serving index page...");
-        ("HTTP/1.1 200 OK".to_string(), get_index_html())
-    } else if request_line.starts_with("OPTIONS") {
+    let (method, segments) = parse_request_line(request_line);
+    let query = parse_query(request_line);
+    let (status_line, contents) = match (method, segments.as_slice()) {
+        ("GET", []) => {
+            println!("📄 Serving index page...");
+            // Served straight from the embedded file, without copying it
+            return ("HTTP/1.1 200 OK".to_string(), Cow::Borrowed(get_index_html()));
+        }
         // Handle CORS preflight requests
-        ("HTTP/1.1 200 OK".to_string(), String::new())
-    } else if request_line.starts_with("POST /api/start") {
-        handle_start_mining(&request, sessions)
-    } else if request_line.starts_with("POST /api/mine") {
-        handle_mine_block(&request, blockchain, sessions)
-    } else if request_line.starts_with("GET /api/blockchain") {
-        handle_blockchain_status(blockchain, sessions)
-    } else if request_line.starts_with("GET /api/history") {
-        handle_mining_history(blockchain, sessions)
-    } else if request_line.starts_with("GET /api/status/") {
-        let session_id = extract_session_id(request_line);
-        handle_get_status(&session_id, sessions)
-    } else {
-        ("HTTP/1.1 404 NOT FOUND".to_string(), "404 Not Found".to_string())
+        ("OPTIONS", _) => ("HTTP/1.1 200 OK".to_string(), String::new()),
+        ("POST", ["api", "start"]) => handle_start_mining(request, state),
+        ("POST", ["api", "mine"]) => handle_mine_block(request, state),
+        // Only reached by requests that didn't ask to upgrade
+        ("GET", ["ws"]) => ("HTTP/1.1 426 UPGRADE REQUIRED".to_string(), "Expected a websocket upgrade".to_string()),
+        ("POST", ["api", "transaction"]) => handle_submit_transaction(request, blockchain),
+        ("GET", ["api", "block", "hash", hash]) => handle_get_block_by_hash(hash, blockchain),
+        ("GET", ["api", "block", index]) => handle_get_block_by_index(index, blockchain),
+        ("GET", ["api", "proof", tx_hash]) => handle_get_transaction_proof(tx_hash, blockchain),
+        ("GET", ["api", "nonce", address]) => handle_get_nonce(address, blockchain),
+        ("GET", ["api", "balance", address]) => handle_get_balance(address, blockchain),
+        ("GET", ["metrics"]) => handle_metrics(blockchain, sessions),
+        ("GET", ["api", "state"]) => handle_get_state(blockchain),
+        ("GET", ["api", "headers"]) => handle_get_headers(blockchain),
+        ("GET", ["api", "difficulty-history"]) => handle_get_difficulty_history(blockchain),
+        ("GET", ["api", "leaderboard"]) => handle_get_leaderboard(blockchain),
+        ("GET", ["api", "mempool"]) => handle_get_mempool(blockchain),
+        ("GET", ["api", "blockchain"]) => handle_blockchain_status(blockchain, sessions),
+        ("GET", ["api", "blocks"]) => handle_get_blocks(&query, blockchain),
+        ("GET", ["api", "chain"]) => handle_get_chain(&query, blockchain),
+        ("GET", ["api", "history"]) => handle_mining_history(&query, sessions),
+        ("GET", ["api", "status", session_id]) => handle_get_status(session_id, sessions),
+        _ => ("HTTP/1.1 404 NOT FOUND".to_string(), "404 Not Found".to_string()),
     };
     
-    let response = format!(
-        "{}\r\nContent-Type: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n\r\n{}",
-        status_line,
-        if contents.starts_with("{") || contents.starts_with("[") { "application/json" } else { "text/html" },
-        contents
-    );
-    
-    stream.write(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    (status_line, Cow::Owned(contents))
+}
+
+/// Splits a request line such as `GET /api/block/3?pretty HTTP/1.1` into
+/// its method and path segments, here `("GET", ["api", "block", "3"])`.
+/// The query string and empty segments, e.g. from a trailing slash, are
+/// dropped, so routes are matched on whole segments only.
+fn parse_request_line(request_line: &str) -> (&str, Vec<&str>) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("");
+    let path = target.split(['?', '#']).next().unwrap_or("");
+    let segments = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    (method, segments)
+}
+
+/// The query string of a request line as decoded name/value pairs, so
+/// `GET /api/history?offset=10&limit=5 HTTP/1.1` gives `offset` => `10` and
+/// `limit` => `5`. A name given twice keeps its last value.
+fn parse_query(request_line: &str) -> HashMap<String, String> {
+    let target = request_line.split_whitespace().nth(1).unwrap_or("");
+    let target = target.split('#').next().unwrap_or("");
+    let Some((_, query)) = target.split_once('?') else {
+        return HashMap::new();
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes and `+` as a space, leaving malformed escapes as
+/// they are
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit));
+        match hex {
+            Some(hex) => {
+                // Two ASCII hex digits, so both conversions succeed
+                let hex = std::str::from_utf8(hex).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            None => {
+                decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The query's `name` parameter as a number, if given, or a 400 if it
+/// isn't one
+fn query_number<T: std::str::FromStr>(query: &HashMap<String, String>, name: &str) -> Result<Option<T>, (String, String)> {
+    query
+        .get(name)
+        .map(|value| value.parse::<T>())
+        .transpose()
+        .map_err(|_| ("HTTP/1.1 400 BAD REQUEST".to_string(), format!("Invalid {}: expected a number", name)))
+}
+
+/// The `offset` and `limit` of a paged request, or a 400 if either isn't a
+/// number
+fn parse_page_query(query: &HashMap<String, String>) -> Result<PageQuery, (String, String)> {
+    Ok(PageQuery { offset: query_number(query, "offset")?, limit: query_number(query, "limit")? })
 }
 
 fn extract_body(request: &str) -> String {
@@ -136,31 +516,27 @@ fn extract_body(request: &str) -> String {
     }
 }
 
-fn extract_session_id(request_line: &str) -> String {
-    if let Some(path) = request_line.split_whitespace().nth(1) {
-        if let Some(id) = path.strip_prefix("/api/status/") {
-            return id.to_string();
-        }
-    }
-    String::new()
-}
-
-fn handle_start_mining(request: &str, sessions: SharedSessions) -> (String, String) {
+fn handle_start_mining(request: &str, state: &ServerState) -> (String, String) {
     let body = extract_body(request);
     println!("Received start mining request body: '{}'", body); // Debug log
     
     if let Ok(req) = serde_json::from_str::<StartMiningRequest>(&body) {
         let session_id = generate_uuid();
-        let session = MinerSession {
-            id: session_id.clone(),
-            name: req.miner_name,
-            total_phlopcoin: 0.0,
-            blocks_mined: 0,
-            mining_history: Vec::new(),
-        };
+        let session = MinerSession::new(session_id.clone(), req.miner_name);
         
-        let mut sessions_guard = sessions.lock().unwrap();
-        sessions_guard.insert(session_id, session.clone());
+        let Ok(mut sessions_guard) = state.sessions.lock() else {
+            return poisoned_lock_response();
+        };
+        sessions_guard.insert(session_id.clone(), session.clone());
+        if let Err(e) = save_sessions(&state.sessions_file, &sessions_guard) {
+            println!("Failed to save sessions: {}", e);
+        }
+
+        let Ok(blockchain_guard) = state.blockchain.lock() else {
+            return poisoned_lock_response();
+        };
+        let status = current_status(&blockchain_guard, sessions_guard.len());
+        state.updates.publish(&LiveUpdate::SessionUpdated { session_id, status });
         
         let response = MiningResponse {
             success: true,
@@ -175,124 +551,283 @@ fn handle_start_mining(request: &str, sessions: SharedSessions) -> (String, Stri
     }
 }
 
-fn handle_mine_block(request: &str, blockchain: SharedBlockchain, sessions: SharedSessions) -> (String, String) {
+fn handle_mine_block(request: &str, state: &ServerState) -> (String, String) {
     let body = extract_body(request);
     println!("Received mine block request body: '{}'", body); // Debug log
     
-    if let Ok(req) = serde_json::from_str::<MineBlockRequest>(&body) {
-        let mut sessions_guard = sessions.lock().unwrap();
-        if let Some(session) = sessions_guard.get_mut(&req.session_id) {
-            // Add a few dummy transactions to make mining more interesting
-            let tx1 = Transaction::new(
-                "alice".to_string(),
-                session.name.clone(),
-                5, // Small amount
-                1,
-            );
-            let tx2 = Transaction::new(
-                session.name.clone(),
-                "bob".to_string(),
-                3, // Small amount
-                session.blocks_mined + 1,
-            );
-            
-            let mut blockchain_guard = blockchain.lock().unwrap();
-            
-            // Add transactions (ignore errors for demo purposes)
-            let _ = blockchain_guard.add_transaction(tx1);
-            let _ = blockchain_guard.add_transaction(tx2);
-            
-            match blockchain_guard.mine_pending_transactions(session.name.clone()) {
-                Ok(block) => {
-                    if let Some(ref rps_result) = block.rps_mining_result {
-                        let min_games_needed = calculate_minimum_games_needed(&blockchain_guard);
-                        let actual_games = rps_result.total_games as f64;
-                        let phlopcoin_earned = min_games_needed / (actual_games * actual_games);
-                        
-                        let mining_result = MiningResult {
-                            block_number: block.index,
-                            phlopcoin_earned,
-                            games_played: rps_result.total_games,
-                            rounds: rps_result.rounds,
-                            timestamp: format_timestamp(std::time::SystemTime::now()),
-                            block_hash: format!("{}", block.hash), // Convert hash to string
-                        };
-                        
-                        session.total_phlopcoin += phlopcoin_earned;
-                        session.blocks_mined += 1;
-                        session.mining_history.push(mining_result.clone());
-                        
-                        let response = MiningResponse {
-                            success: true,
-                            message: format!("Block #{} mined successfully! Earned {:.6} PhlopCoin", block.index, phlopcoin_earned),
-                            session: Some(session.clone()),
-                            mining_result: Some(mining_result),
-                        };
-                        
-                        ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
-                    } else {
-                        let response = MiningResponse {
-                            success: false,
-                            message: "Mining failed - no RPS result".to_string(),
-                            session: Some(session.clone()),
-                            mining_result: None,
-                        };
-                        ("HTTP/1.1 500 INTERNAL SERVER ERROR".to_string(), serde_json::to_string(&response).unwrap())
-                    }
-                }
-                Err(e) => {
-                    let response = MiningResponse {
-                        success: false,
-                        message: format!("Mining failed: {}", e),
-                        session: Some(session.clone()),
-                        mining_result: None,
-                    };
-                    ("HTTP/1.1 500 INTERNAL SERVER ERROR".to_string(), serde_json::to_string(&response).unwrap())
-                }
+    let Ok(req) = serde_json::from_str::<MineBlockRequest>(&body) else {
+        return ("HTTP/1.1 400 BAD REQUEST".to_string(), "Invalid request".to_string());
+    };
+    let Ok(mut sessions_guard) = state.sessions.lock() else {
+        return poisoned_lock_response();
+    };
+    let active_miners = sessions_guard.len();
+    let Some(session) = sessions_guard.get_mut(&req.session_id) else {
+        let response = MiningResponse::failure("Session not found".to_string(), None);
+        return ("HTTP/1.1 404 NOT FOUND".to_string(), serde_json::to_string(&response).unwrap());
+    };
+    let Ok(mut blockchain_guard) = state.blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    match mine_for_session(&mut blockchain_guard, session) {
+        Ok((block, mining_result)) => {
+            let response = MiningResponse {
+                success: true,
+                message: format!("Block #{} mined successfully! Earned {:.6} PhlopCoin", block.index, mining_result.phlopcoin_earned),
+                session: Some(session.clone()),
+                mining_result: Some(mining_result),
+            };
+
+            if let Err(e) = blockchain_guard.save_to_file(&state.chain_file) {
+                println!("Failed to save blockchain: {}", e);
             }
-        } else {
-            ("HTTP/1.1 404 NOT FOUND".to_string(), "Session not found".to_string())
+            let status = current_status(&blockchain_guard, active_miners);
+            drop(blockchain_guard);
+
+            state.updates.publish(&LiveUpdate::BlockMined { block_index: block.index, status: status.clone() });
+            state.updates.publish(&LiveUpdate::SessionUpdated { session_id: session.id.clone(), status });
+            if let Err(e) = save_sessions(&state.sessions_file, &sessions_guard) {
+                println!("Failed to save sessions: {}", e);
+            }
+
+            ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
         }
-    } else {
-        ("HTTP/1.1 400 BAD REQUEST".to_string(), "Invalid request".to_string())
+        Err(e) => {
+            let response = MiningResponse::failure(format!("Mining failed: {}", e), Some(session.clone()));
+            ("HTTP/1.1 500 INTERNAL SERVER ERROR".to_string(), serde_json::to_string(&response).unwrap())
+        }
+    }
+}
+
+fn handle_submit_transaction(request: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let rejected = |(code, reason): (u16, &str), message: String| {
+        let response = TransactionResponse {
+            success: false,
+            message,
+            tx_hash: None,
+        };
+        (format!("HTTP/1.1 {} {}", code, reason), serde_json::to_string(&response).unwrap())
+    };
+
+    let body = extract_body(request);
+    let req = match serde_json::from_str::<SubmitTransactionRequest>(&body) {
+        Ok(req) => req,
+        Err(e) => return rejected((400, "BAD REQUEST"), format!("Invalid transaction request: {}", e)),
+    };
+
+    let tx = Transaction::new_with_fee(req.from, req.to, req.amount, req.fee, req.nonce);
+    let tx_hash = tx.hash.to_hex();
+
+    let Ok(mut blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    match blockchain_guard.add_transaction(tx) {
+        Ok(()) => {
+            let response = TransactionResponse {
+                success: true,
+                message: "Transaction added to the mempool".to_string(),
+                tx_hash: Some(tx_hash),
+            };
+            ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
+        }
+        Err(e) => rejected(rejection_status(&e), e.to_string()),
+    }
+}
+
+/// The full block, merkle root and RPS result included, or 404
+fn block_response(block: Option<&Block>) -> (String, String) {
+    match block {
+        Some(block) => ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(block).unwrap()),
+        None => ("HTTP/1.1 404 NOT FOUND".to_string(), "Block not found".to_string()),
     }
 }
 
+fn handle_get_block_by_index(index: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let block = index.parse().ok().and_then(|index| blockchain_guard.get_block_by_index(index));
+    block_response(block)
+}
+
+fn handle_get_block_by_hash(hash: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let block = Hash::from_hex(hash)
+        .ok()
+        .and_then(|hash| blockchain_guard.get_block_by_hash(&hash));
+    block_response(block)
+}
+
+/// Merkle inclusion proof for a mined transaction, enough for a light client
+/// to check it against the block's merkle root
+fn handle_get_transaction_proof(tx_hash: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let not_found = || ("HTTP/1.1 404 NOT FOUND".to_string(), "Transaction not found".to_string());
+
+    let Ok(hash) = Hash::from_hex(tx_hash) else {
+        return not_found();
+    };
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let Some((proof, tx_index, block_index)) = blockchain_guard.get_transaction_proof(&hash) else {
+        return not_found();
+    };
+    let block = blockchain_guard
+        .get_block_by_index(block_index)
+        .expect("proof refers to a block in the chain");
+
+    let response = ProofResponse {
+        tx_hash: tx_hash.to_string(),
+        tx_index,
+        block_index,
+        merkle_root: block.merkle_root.to_hex(),
+        proof: proof.iter().map(|hash| hash.to_hex()).collect(),
+    };
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
+}
+
+fn handle_get_balance(address: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    // Unknown accounts hold nothing rather than being missing
+    let response = BalanceResponse {
+        address: address.to_string(),
+        balance: blockchain_guard.get_balance(&address.to_string()),
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
+}
+
+/// Where an account's nonce stands: `nonce` is its last mined transaction's,
+/// `next_nonce` what its next transaction must carry
+fn handle_get_nonce(address: &str, blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let address = address.to_string();
+    let response = NonceResponse {
+        nonce: blockchain_guard.get_nonce(&address),
+        next_nonce: blockchain_guard.get_next_nonce(&address),
+        address,
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&response).unwrap())
+}
+
+fn handle_metrics(blockchain: SharedBlockchain, sessions: SharedSessions) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let Ok(sessions_guard) = sessions.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), render_metrics(&blockchain_guard, sessions_guard.len()))
+}
+
+fn handle_get_state(blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&state_response(&blockchain_guard)).unwrap())
+}
+
+fn handle_get_difficulty_history(blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&difficulty_history_response(&blockchain_guard)).unwrap())
+}
+
+fn handle_get_headers(blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&blockchain_guard.get_headers()).unwrap())
+}
+
+fn handle_get_leaderboard(blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&leaderboard_response(&blockchain_guard)).unwrap())
+}
+
+fn handle_get_mempool(blockchain: SharedBlockchain) -> (String, String) {
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&mempool_response(&blockchain_guard)).unwrap())
+}
+
 fn handle_blockchain_status(blockchain: SharedBlockchain, sessions: SharedSessions) -> (String, String) {
-    let blockchain_guard = blockchain.lock().unwrap();
-    let sessions_guard = sessions.lock().unwrap();
-    
-    let status = BlockchainStatus {
-        total_blocks: blockchain_guard.get_chain_length(),
-        total_games_played: blockchain_guard.get_total_rps_games(),
-        current_difficulty_score: blockchain_guard.get_rps_difficulty_info().difficulty_score(),
-        active_miners: sessions_guard.len(),
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+    let Ok(sessions_guard) = sessions.lock() else {
+        return poisoned_lock_response();
     };
     
+    let status = current_status(&blockchain_guard, sessions_guard.len());
+    
     ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&status).unwrap())
 }
 
-fn handle_mining_history(_blockchain: SharedBlockchain, sessions: SharedSessions) -> (String, String) {
-    let sessions_guard = sessions.lock().unwrap();
-    
-    // Collect all mining history from all sessions
-    let mut all_mining_history: Vec<MiningResult> = Vec::new();
-    
-    for session in sessions_guard.values() {
-        all_mining_history.extend(session.mining_history.clone());
-    }
-    
-    // Sort by block number (newest first)
-    all_mining_history.sort_by(|a, b| b.block_number.cmp(&a.block_number));
+fn handle_get_blocks(query: &HashMap<String, String>, blockchain: SharedBlockchain) -> (String, String) {
+    let page_query = match parse_page_query(query) {
+        Ok(page_query) => page_query,
+        Err(response) => return response,
+    };
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&blocks_page(&blockchain_guard, &page_query)).unwrap())
+}
+
+/// Whole blocks for export, optionally only those with indices in
+/// `from..to`
+fn handle_get_chain(query: &HashMap<String, String>, blockchain: SharedBlockchain) -> (String, String) {
+    let (from, to) = match (query_number(query, "from"), query_number(query, "to")) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(response), _) | (_, Err(response)) => return response,
+    };
+    let Ok(blockchain_guard) = blockchain.lock() else {
+        return poisoned_lock_response();
+    };
+
+    let blocks = blockchain_guard.get_blocks_in_range(from.unwrap_or(0), to.unwrap_or(u32::MAX));
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(blocks).unwrap())
+}
+
+fn handle_mining_history(query: &HashMap<String, String>, sessions: SharedSessions) -> (String, String) {
+    let page_query = match parse_page_query(query) {
+        Ok(page_query) => page_query,
+        Err(response) => return response,
+    };
+    let Ok(sessions_guard) = sessions.lock() else {
+        return poisoned_lock_response();
+    };
     
-    // Take last 20 blocks for charts
-    let recent_history: Vec<MiningResult> = all_mining_history.into_iter().take(20).collect();
+    // The latest blocks across every session, for the charts
+    let history = mining_history_page(&sessions_guard, &page_query);
     
-    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&recent_history).unwrap())
+    ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(&history).unwrap())
 }
 
 fn handle_get_status(session_id: &str, sessions: SharedSessions) -> (String, String) {
-    let sessions_guard = sessions.lock().unwrap();
+    let Ok(sessions_guard) = sessions.lock() else {
+        return poisoned_lock_response();
+    };
     if let Some(session) = sessions_guard.get(session_id) {
         ("HTTP/1.1 200 OK".to_string(), serde_json::to_string(session).unwrap())
     } else {
@@ -300,35 +835,840 @@ fn handle_get_status(session_id: &str, sessions: SharedSessions) -> (String, Str
     }
 }
 
-fn calculate_minimum_games_needed(blockchain: &Blockchain) -> f64 {
-    let difficulty_info = blockchain.get_rps_difficulty_info();
-    let mut min_games = 0.0;
-    
-    for (&required_wins, &player_count) in &difficulty_info.win_distribution {
-        min_games += (required_wins as f64) * (player_count as f64);
-    }
-    
-    min_games.max(1.0)
+/// Sent when a handler needs a lock that another thread panicked while
+/// holding, instead of panicking this connection's thread as well
+fn poisoned_lock_response() -> (String, String) {
+    let response = serde_json::json!({
+        "success": false,
+        "message": "Server state is unavailable after an internal error",
+    });
+    ("HTTP/1.1 500 INTERNAL SERVER ERROR".to_string(), response.to_string())
 }
 
+/// Session id that is unique within this process thanks to the counter, and
+/// across restarts thanks to the timestamp and the randomly keyed hash, which
+/// also keeps ids from being guessed
 fn generate_uuid() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
     use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
-    format!("miner_{}", timestamp)
-}
 
-fn format_timestamp(time: std::time::SystemTime) -> String {
-    use std::time::UNIX_EPOCH;
-    let duration = time.duration_since(UNIX_EPOCH).unwrap();
-    let secs = duration.as_secs();
-    
-    // Simple timestamp formatting
-    format!("{} seconds since epoch", secs)
+    static SESSIONS_STARTED: AtomicU64 = AtomicU64::new(0);
+    let count = SESSIONS_STARTED.fetch_add(1, Ordering::Relaxed);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos()).unwrap_or(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(timestamp);
+    hasher.write_u64(count);
+    format!("miner_{}_{}_{:016x}", timestamp, count, hasher.finish())
 }
 
-fn get_index_html() -> String {
-    println!("🔍 Loading index.html...");
+fn get_index_html() -> &'static str {
     let html_content = include_str!("../static/index.html");
-    println!("✅ Successfully loaded HTML file ({} bytes)", html_content.len());
-    html_content.to_string()
+    println!("✅ Serving HTML file ({} bytes)", html_content.len());
+    html_content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fresh server whose chain and sessions files are in the temp
+    /// directory, out of the way of a real server's
+    fn test_state() -> ServerState {
+        static SERVERS: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("phlopchain_web_main_{}_{}", std::process::id(), SERVERS.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir();
+        ServerState::load(dir.join(format!("{}_chain.json", name)), dir.join(format!("{}_sessions.json", name)))
+    }
+
+    /// Sends `request` to a server on a real socket and returns the raw
+    /// response, headers and all
+    fn send_request(state: &ServerState, request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_state = state.clone();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, &server_state);
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        server.join().unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    /// Drops the files a test's server saved
+    fn remove_files(state: &ServerState) {
+        let _ = std::fs::remove_file(&state.chain_file);
+        let _ = std::fs::remove_file(&state.sessions_file);
+    }
+
+    /// Hands out the data a few bytes at a time, like a request split
+    /// across several TCP segments
+    struct TrickleReader {
+        data: Vec<u8>,
+        position: usize,
+        chunk_size: usize,
+    }
+
+    impl Read for TrickleReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let end = (self.position + self.chunk_size).min(self.data.len());
+            let len = (end - self.position).min(buf.len());
+            buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+    }
+
+    fn post_request(path: &str, body: &str, content_length: &str) -> TrickleReader {
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            path, content_length, body
+        );
+        TrickleReader {
+            data: request.into_bytes(),
+            position: 0,
+            chunk_size: 1000,
+        }
+    }
+
+    #[test]
+    fn test_read_large_request_body() {
+        let miner_name = "m".repeat(20_000);
+        let body = serde_json::json!({ "miner_name": miner_name }).to_string();
+        let mut reader = post_request("/api/start", &body, &body.len().to_string());
+
+        let request = read_request(&mut reader).unwrap();
+        let parsed: StartMiningRequest = serde_json::from_str(&extract_body(&request)).unwrap();
+        assert_eq!(parsed.miner_name, miner_name);
+    }
+
+    #[test]
+    fn test_read_request_rejects_bad_content_length() {
+        let mut reader = post_request("/api/start", "{}", "lots");
+        assert!(read_request(&mut reader).is_err());
+
+        let mut reader = post_request("/api/start", "{}", "200");
+        assert!(read_request(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_read_transaction_body_split_across_reads() {
+        let body = serde_json::json!({
+            "from": "alice",
+            "to": "bob",
+            "amount": 10,
+            "nonce": 1,
+            "memo": "x".repeat(5 * 1024),
+        })
+        .to_string();
+        let mut reader = post_request("/api/transaction", &body, &body.len().to_string());
+
+        let request = read_request(&mut reader).unwrap();
+        let state = test_state();
+        let (status_line, _) = route_request(&request, &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(state.blockchain.lock().unwrap().get_pending_transaction_count(), 1);
+    }
+
+    #[test]
+    fn test_read_request_rejects_oversized_body() {
+        // Refused from the header alone, without waiting for the body
+        let mut reader = post_request("/api/transaction", "", &(MAX_BODY_BYTES + 1).to_string());
+        let error = read_request(&mut reader).unwrap_err();
+        assert_eq!(error, RequestError::BodyTooLarge);
+        assert_eq!(error.status_line(), "HTTP/1.1 413 PAYLOAD TOO LARGE");
+    }
+
+    #[test]
+    fn test_poisoned_blockchain_returns_500() {
+        let state = test_state();
+
+        let poisoner = Arc::clone(&state.blockchain);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the blockchain lock");
+        })
+        .join();
+        assert!(state.blockchain.is_poisoned());
+
+        let (status_line, contents) = route_request("GET /api/blockchain HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 500 INTERNAL SERVER ERROR");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["success"], false);
+    }
+
+    /// Accepts at most a few bytes per write, like a socket with a full send buffer
+    struct TrickleWriter {
+        written: Vec<u8>,
+        max_write: usize,
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.max_write);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_index_html_fully_written_on_short_writes() {
+        let html = get_index_html();
+        let mut writer = TrickleWriter {
+            written: Vec::new(),
+            max_write: 7,
+        };
+
+        write_response(&mut writer, "HTTP/1.1 200 OK", html, None).unwrap();
+
+        let response = String::from_utf8(writer.written).unwrap();
+        let (headers, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(headers.contains(&format!("Content-Length: {}", html.len())));
+        assert_eq!(body.len(), html.len());
+        assert_eq!(body, html);
+    }
+
+    #[test]
+    fn test_cors_only_echoes_allowed_origins() {
+        let request = "GET /api/blockchain HTTP/1.1\r\nHost: localhost\r\nOrigin: http://localhost:3030\r\n\r\n";
+        assert_eq!(allowed_origin(request, &allowed_origins(None)), Some("http://localhost:3030"));
+
+        let request = "GET /api/blockchain HTTP/1.1\r\nHost: localhost\r\norigin: https://evil.example\r\n\r\n";
+        let origin = allowed_origin(request, &allowed_origins(None));
+        assert_eq!(origin, None);
+
+        let mut response = Vec::new();
+        write_response(&mut response, "HTTP/1.1 200 OK", "{}", origin).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+
+        let mut response = Vec::new();
+        write_response(&mut response, "HTTP/1.1 200 OK", "{}", Some("http://localhost:3030")).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:3030\r\n"));
+    }
+
+    #[test]
+    fn test_submit_transaction() {
+        let state = test_state();
+        let submit = |body: &str| {
+            let request = format!("POST /api/transaction HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            let (status_line, contents) = route_request(&request, &state);
+            (status_line, serde_json::from_str::<serde_json::Value>(&contents).unwrap())
+        };
+
+        let (status_line, json) = submit(r#"{"from":"alice","to":"bob","amount":100,"nonce":1}"#);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(json["success"], true);
+        assert_eq!(json["tx_hash"].as_str().unwrap().len(), 64);
+        assert_eq!(state.blockchain.lock().unwrap().get_pending_transaction_count(), 1);
+
+        let (status_line, json) = submit(r#"{"from":"bob","to":"alice","amount":1000000,"nonce":1}"#);
+        assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+        assert_eq!(json["message"], "Insufficient balance");
+
+        let (status_line, json) = submit(r#"{"from":"alice","to":"bob","amount":1,"nonce":1}"#);
+        assert_eq!(status_line, "HTTP/1.1 409 CONFLICT");
+        assert_eq!(json["message"], "Invalid nonce");
+
+        // A future nonce is queued rather than rejected
+        let (status_line, _) = submit(r#"{"from":"alice","to":"bob","amount":1,"nonce":7}"#);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(state.blockchain.lock().unwrap().get_queued_transaction_count(), 1);
+
+        // System accounts can't be spent from, even with a nonce that would be queued
+        for body in [
+            r#"{"from":"network","to":"mallory","amount":1000,"nonce":1}"#,
+            r#"{"from":"genesis","to":"mallory","amount":1000,"nonce":5}"#,
+        ] {
+            let (status_line, json) = submit(body);
+            assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+            assert!(json["message"].as_str().unwrap().contains("reserved account"));
+        }
+        assert_eq!(state.blockchain.lock().unwrap().get_queued_transaction_count(), 1);
+
+        let (status_line, json) = submit(r#"{"from":"alice""#);
+        assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+        assert_eq!(json["success"], false);
+    }
+
+    #[test]
+    fn test_get_balance() {
+        let state = test_state();
+        let get_balance = |address: &str| {
+            let request = format!("GET /api/balance/{} HTTP/1.1\r\n\r\n", address);
+            let (status_line, contents) = route_request(&request, &state);
+            assert_eq!(status_line, "HTTP/1.1 200 OK");
+            serde_json::from_str::<serde_json::Value>(&contents).unwrap()
+        };
+
+        let json = get_balance("bob");
+        assert_eq!(json["address"], "bob");
+        assert_eq!(json["balance"], 500);
+        assert_eq!(get_balance("nobody")["balance"], 0);
+    }
+
+    #[test]
+    fn test_get_block_by_index_and_hash() {
+        let state = test_state();
+        let block = state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+        let get = |path: &str| {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            route_request(&request, &state)
+        };
+
+        let (status_line, contents) = get("/api/block/1");
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["hash"], block.hash.to_hex());
+        assert_eq!(json["merkle_root"], block.merkle_root.to_hex());
+        assert!(json["rps_mining_result"].is_object());
+
+        let (status_line, contents) = get(&format!("/api/block/hash/{}", block.hash.to_hex()));
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["index"], 1);
+
+        for path in ["/api/block/2", "/api/block/first", "/api/block/hash/not-hex"] {
+            assert_eq!(get(path).0, "HTTP/1.1 404 NOT FOUND", "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_get_transaction_proof() {
+        let state = test_state();
+        let tx = Transaction::new("alice".to_string(), "bob".to_string(), 100, 1);
+        {
+            let mut blockchain = state.blockchain.lock().unwrap();
+            blockchain.add_transaction(tx.clone()).unwrap();
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let get = |path: String| {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            route_request(&request, &state)
+        };
+
+        let (status_line, contents) = get(format!("/api/proof/{}", tx.hash.to_hex()));
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["block_index"], 1);
+
+        // Everything needed to verify comes with the response
+        let proof: Vec<Hash> = json["proof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hash| Hash::from_hex(hash.as_str().unwrap()).unwrap())
+            .collect();
+        let root = Hash::from_hex(json["merkle_root"].as_str().unwrap()).unwrap();
+        let tx_index = json["tx_index"].as_u64().unwrap() as usize;
+        assert!(merkle::verify_proof_against_root(&tx.hash, &proof, tx_index, &root));
+
+        assert_eq!(get(format!("/api/proof/{}", Hash::from_string("unknown").to_hex())).0, "HTTP/1.1 404 NOT FOUND");
+        assert_eq!(get("/api/proof/not-hex".to_string()).0, "HTTP/1.1 404 NOT FOUND");
+    }
+
+    #[test]
+    fn test_get_nonce() {
+        let state = test_state();
+        let get_nonce = || {
+            let (status_line, contents) =
+                route_request("GET /api/nonce/alice HTTP/1.1\r\n\r\n", &state);
+            assert_eq!(status_line, "HTTP/1.1 200 OK");
+            serde_json::from_str::<serde_json::Value>(&contents).unwrap()
+        };
+
+        let json = get_nonce();
+        assert_eq!(json["address"], "alice");
+        assert_eq!(json["nonce"], 0);
+        assert_eq!(json["next_nonce"], 1);
+
+        state.blockchain.lock().unwrap().add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 1)).unwrap();
+        state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+        let json = get_nonce();
+        assert_eq!(json["nonce"], 1);
+        assert_eq!(json["next_nonce"], 2);
+    }
+
+    #[test]
+    fn test_bind_busy_port_fails_cleanly() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+
+        let error = bind_listener(addr).unwrap_err();
+        assert!(error.starts_with(&format!("Could not bind to {}:", addr)), "{}", error);
+    }
+
+    #[test]
+    fn test_mine_unknown_session_is_404() {
+        let state = test_state();
+        let body = r#"{"session_id":"nobody"}"#;
+        let request = format!("POST /api/mine HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+
+        let (status_line, contents) = route_request(&request, &state);
+        assert_eq!(status_line, "HTTP/1.1 404 NOT FOUND");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["message"], "Session not found");
+    }
+
+    #[test]
+    fn test_session_ids_are_unique() {
+        let ids: std::collections::HashSet<String> = (0..10_000).map(|_| generate_uuid()).collect();
+        assert_eq!(ids.len(), 10_000);
+        assert!(ids.iter().all(|id| id.starts_with("miner_")));
+    }
+
+    #[test]
+    fn test_get_mempool() {
+        let state = test_state();
+        let tx = Transaction::new("alice".to_string(), "bob".to_string(), 10, 1);
+        state.blockchain.lock().unwrap().add_transaction(tx.clone()).unwrap();
+        state.blockchain.lock().unwrap().add_transaction(Transaction::new("alice".to_string(), "bob".to_string(), 10, 3)).unwrap();
+
+        let (status_line, contents) = route_request("GET /api/mempool HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["queued_count"], 1);
+        assert_eq!(json["transactions"][0]["hash"], tx.hash.to_hex());
+        assert_eq!(json["transactions"][0]["amount"], 10);
+    }
+
+    #[test]
+    fn test_metrics_are_plain_text() {
+        let state = test_state();
+        let (status_line, contents) = route_request("GET /metrics HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert!(contents.contains("phlopchain_chain_length 1\n"));
+
+        let mut response = Vec::new();
+        write_response(&mut response, &status_line, &contents, None).unwrap();
+        assert!(String::from_utf8(response).unwrap().contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    }
+
+    #[test]
+    fn test_get_leaderboard() {
+        let state = test_state();
+        state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+
+        let (status_line, contents) = route_request("GET /api/leaderboard HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json[0]["address"], "miner");
+        assert_eq!(json[0]["rewards"], 100);
+    }
+
+    #[test]
+    fn test_get_headers() {
+        let state = test_state();
+        state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+
+        let (status_line, contents) = route_request("GET /api/headers HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        let headers = json.as_array().unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[1]["hash"], state.blockchain.lock().unwrap().get_latest_block().hash.to_hex());
+        assert!(headers[1].get("transactions").is_none());
+    }
+
+    #[test]
+    fn test_get_difficulty_history() {
+        let state = test_state();
+        for _ in 0..3 {
+            state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+        }
+
+        let (status_line, contents) = route_request("GET /api/difficulty-history HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        let points = json.as_array().unwrap();
+        assert_eq!(points.iter().map(|point| point["block_index"].as_u64().unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let scores = state.blockchain.lock().unwrap().get_difficulty_history().to_vec();
+        assert_eq!(points[2]["difficulty_score"].as_f64().unwrap(), scores[2].1);
+    }
+
+    #[test]
+    fn test_get_state() {
+        let state = test_state();
+        state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+
+        let (status_line, contents) = route_request("GET /api/state HTTP/1.1\r\n\r\n", &state);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(json["state_root"], state.blockchain.lock().unwrap().get_latest_block().state_root.to_hex());
+        assert_eq!(json["balances"]["miner"], 100);
+        assert_eq!(json["balances"]["alice"], 1000);
+    }
+
+    #[test]
+    fn test_routes_match_whole_segments() {
+        let state = test_state();
+        let status = |request_line: &str| {
+            let request = format!("{}\r\n\r\n", request_line);
+            route_request(&request, &state).0
+        };
+
+        assert_eq!(status("GET /api/blockchain HTTP/1.1"), "HTTP/1.1 200 OK");
+        assert_eq!(status("GET /api/blockchain/ HTTP/1.1"), "HTTP/1.1 200 OK");
+        assert_eq!(status("GET /api/blockchain?verbose=1 HTTP/1.1"), "HTTP/1.1 200 OK");
+
+        // Used to match /api/blockchain and /api/mempool by prefix
+        assert_eq!(status("GET /api/blockchain-extra HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+        assert_eq!(status("GET /api/mempoolx HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+        assert_eq!(status("GET /api/balance/alice/extra HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+        assert_eq!(status("GET /api/balance/ HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+
+        // The method has to match too
+        assert_eq!(status("POST /api/blockchain HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+        assert_eq!(status("GET /api/mine HTTP/1.1"), "HTTP/1.1 404 NOT FOUND");
+    }
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(parse_request_line("GET / HTTP/1.1"), ("GET", vec![]));
+        assert_eq!(
+            parse_request_line("GET /api/block/hash/ab12?x=1 HTTP/1.1"),
+            ("GET", vec!["api", "block", "hash", "ab12"])
+        );
+        assert_eq!(parse_request_line(""), ("", vec![]));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("GET /api/history?offset=10&limit=5 HTTP/1.1");
+        assert_eq!(query.len(), 2);
+        assert_eq!(query["offset"], "10");
+        assert_eq!(query["limit"], "5");
+
+        let query = parse_query("GET /api/x?name=a%20b+c&flag&pct=100%&&name2=%41#frag HTTP/1.1");
+        assert_eq!(query["name"], "a b c");
+        assert_eq!(query["flag"], "");
+        assert_eq!(query["pct"], "100%");
+        assert_eq!(query["name2"], "A");
+        assert_eq!(query.len(), 4);
+
+        assert!(parse_query("GET /api/history HTTP/1.1").is_empty());
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn test_paged_endpoints() {
+        let state = test_state();
+        let mut session = MinerSession::new("session-1".to_string(), "pager".to_string());
+        {
+            let mut blockchain = state.blockchain.lock().unwrap();
+            for _ in 0..20 {
+                mine_for_session(&mut blockchain, &mut session).unwrap();
+            }
+        }
+        state.sessions.lock().unwrap().insert(session.id.clone(), session);
+        let get = |path: &str| {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            route_request(&request, &state)
+        };
+
+        let (status_line, contents) = get("/api/blocks?offset=10&limit=5");
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        let page: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(page["total"], 21);
+        assert_eq!(page["offset"], 10);
+        assert_eq!(page["limit"], 5);
+        let indices: Vec<u64> = page["items"].as_array().unwrap().iter().map(|block| block["index"].as_u64().unwrap()).collect();
+        assert_eq!(indices, vec![10, 11, 12, 13, 14]);
+
+        // History is newest first
+        let (_, contents) = get("/api/history?offset=10&limit=5");
+        let page: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(page["total"], 20);
+        let blocks: Vec<u64> = page["items"].as_array().unwrap().iter().map(|result| result["block_number"].as_u64().unwrap()).collect();
+        assert_eq!(blocks, vec![10, 9, 8, 7, 6]);
+
+        // Defaults, the limit cap, and paging off the end
+        let (_, contents) = get("/api/blocks");
+        let page: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!((page["offset"].as_u64(), page["limit"].as_u64()), (Some(0), Some(DEFAULT_PAGE_LIMIT as u64)));
+        assert_eq!(page["items"].as_array().unwrap().len(), DEFAULT_PAGE_LIMIT);
+        let (_, contents) = get("/api/blocks?limit=1000000");
+        let page: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(page["limit"], MAX_PAGE_LIMIT);
+        assert_eq!(page["items"].as_array().unwrap().len(), 21);
+        let (_, contents) = get("/api/history?offset=50");
+        let page: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(page["total"], 20);
+        assert!(page["items"].as_array().unwrap().is_empty());
+
+        let (status_line, contents) = get("/api/blocks?offset=-1");
+        assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+        assert_eq!(contents, "Invalid offset: expected a number");
+        let (status_line, _) = get("/api/history?limit=ten");
+        assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+    }
+
+    /// Reads one unmasked text frame, as the server sends them
+    fn read_text_frame(stream: &mut TcpStream) -> serde_json::Value {
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 0x81);
+        let len = match header[1] {
+            126 => {
+                let mut len = [0; 2];
+                stream.read_exact(&mut len).unwrap();
+                u16::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).unwrap();
+        serde_json::from_slice(&payload).unwrap()
+    }
+
+    #[test]
+    fn test_websocket_receives_block_mined() {
+        let state = test_state();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_state = state.clone();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, &server_state);
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        client
+            .write_all(b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+            .unwrap();
+        let mut handshake = Vec::new();
+        while !handshake.ends_with(b"\r\n\r\n") {
+            let mut byte = [0; 1];
+            client.read_exact(&mut byte).unwrap();
+            handshake.push(byte[0]);
+        }
+        let handshake = String::from_utf8(handshake).unwrap();
+        assert!(handshake.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(handshake.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert_eq!(state.updates.subscriber_count(), 1);
+
+        let post = |path: &str, body: &str| {
+            let request = format!("POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body);
+            let (status_line, contents) = route_request(&request, &state);
+            assert_eq!(status_line, "HTTP/1.1 200 OK");
+            serde_json::from_str::<serde_json::Value>(&contents).unwrap()
+        };
+        let started = post("/api/start", r#"{"miner_name":"ws-miner"}"#);
+        let session_id = started["session"]["id"].as_str().unwrap().to_string();
+        let update = read_text_frame(&mut client);
+        assert_eq!(update["event"], "session_updated");
+        assert_eq!(update["session_id"], session_id);
+
+        post("/api/mine", &format!(r#"{{"session_id":"{}"}}"#, session_id));
+        let update = read_text_frame(&mut client);
+        assert_eq!(update["event"], "block_mined");
+        assert_eq!(update["block_index"], 1);
+        assert_eq!(update["status"]["total_blocks"], 2);
+        assert_eq!(read_text_frame(&mut client)["event"], "session_updated");
+
+        // Once the client hangs up its subscription goes with it
+        drop(client);
+        server.join().unwrap();
+        state.updates.publish(&LiveUpdate::SessionUpdated {
+            session_id,
+            status: current_status(&state.blockchain.lock().unwrap(), 1),
+        });
+        assert_eq!(state.updates.subscriber_count(), 0);
+
+        // Without the upgrade headers there is nothing to serve
+        assert_eq!(route_request("GET /ws HTTP/1.1\r\n\r\n", &state).0, "HTTP/1.1 426 UPGRADE REQUIRED");
+        remove_files(&state);
+    }
+
+    #[test]
+    fn test_submit_transaction_over_tcp() {
+        let state = test_state();
+        let submit = |body: &str| {
+            let request = format!("POST /api/transaction HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            let response = send_request(&state, &request);
+            let (headers, body) = response.split_once("\r\n\r\n").unwrap();
+            (headers.to_string(), serde_json::from_str::<serde_json::Value>(body).unwrap())
+        };
+
+        let (headers, json) = submit(r#"{"from":"alice","to":"bob","amount":100,"nonce":1,"fee":2}"#);
+        assert!(headers.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(headers.contains("Content-Type: application/json\r\n"));
+        let tx_hash = Hash::from_hex(json["tx_hash"].as_str().unwrap()).unwrap();
+        {
+            let blockchain = state.blockchain.lock().unwrap();
+            let pending = blockchain.get_pending_transactions();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].hash, tx_hash);
+            assert_eq!(pending[0].fee, 2);
+        }
+
+        let (headers, json) = submit(r#"{"from":"bob","to":"alice","amount":1000000,"nonce":1}"#);
+        assert!(headers.starts_with("HTTP/1.1 400 BAD REQUEST\r\n"));
+        assert_eq!(json["success"], false);
+        assert_eq!(json["message"], "Insufficient balance");
+
+        // Malformed bodies are refused before anything reaches the chain
+        for body in [
+            r#"{"from":"alice","to":"bob","nonce":2}"#,
+            r#"{"from":"alice","to":"bob","amount":"lots","nonce":2}"#,
+            r#"{"from":"alice","to":"bob","amount":-5,"nonce":2}"#,
+            "not json",
+        ] {
+            let (headers, json) = submit(body);
+            assert!(headers.starts_with("HTTP/1.1 400 BAD REQUEST\r\n"), "{}", body);
+            assert!(json["message"].as_str().unwrap().starts_with("Invalid transaction request"), "{}", body);
+        }
+        assert_eq!(state.blockchain.lock().unwrap().get_pending_transaction_count(), 1);
+    }
+
+    #[test]
+    fn test_transaction_proofs_over_tcp() {
+        let state = test_state();
+        let transactions: Vec<Transaction> = (1..=3)
+            .map(|nonce| Transaction::new("alice".to_string(), "bob".to_string(), 10, nonce))
+            .collect();
+        {
+            let mut blockchain = state.blockchain.lock().unwrap();
+            for tx in &transactions {
+                blockchain.add_transaction(tx.clone()).unwrap();
+            }
+            blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let get = |tx_hash: &str| {
+            // From the page itself, as a browser light client would ask
+            let request = format!("GET /api/proof/{} HTTP/1.1\r\nOrigin: http://localhost:3030\r\n\r\n", tx_hash);
+            let response = send_request(&state, &request);
+            let (headers, body) = response.split_once("\r\n\r\n").unwrap();
+            (headers.to_string(), body.to_string())
+        };
+
+        // Each transaction's proof checks out against the block it's in
+        for tx in &transactions {
+            let (headers, body) = get(&tx.hash.to_hex());
+            assert!(headers.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(headers.contains("Access-Control-Allow-Origin: http://localhost:3030\r\n"));
+            let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(json["tx_hash"], tx.hash.to_hex());
+            assert_eq!(json["block_index"], 1);
+
+            let proof: Vec<Hash> = json["proof"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|hash| Hash::from_hex(hash.as_str().unwrap()).unwrap())
+                .collect();
+            let tx_index = json["tx_index"].as_u64().unwrap() as usize;
+            let blockchain = state.blockchain.lock().unwrap();
+            assert_eq!(json["merkle_root"], blockchain.get_block_by_index(1).unwrap().merkle_root.to_hex());
+            assert!(blockchain.verify_transaction_proof(tx, &proof, tx_index, 1));
+        }
+
+        let (headers, body) = get(&Hash::from_string("unknown").to_hex());
+        assert!(headers.starts_with("HTTP/1.1 404 NOT FOUND\r\n"));
+        assert_eq!(body, "Transaction not found");
+    }
+
+    #[test]
+    fn test_configured_origins_over_tcp() {
+        let state = test_state().with_allowed_origins(vec!["https://phlopchain.example".to_string()]);
+        let headers_for = |origin: &str| {
+            let request = format!("GET /api/blockchain HTTP/1.1\r\nOrigin: {}\r\n\r\n", origin);
+            let response = send_request(&state, &request);
+            response.split_once("\r\n\r\n").unwrap().0.to_string()
+        };
+
+        let headers = headers_for("https://phlopchain.example");
+        assert!(headers.contains("Access-Control-Allow-Origin: https://phlopchain.example\r\n"));
+
+        // The localhost default no longer applies once origins are configured
+        for origin in ["http://localhost:3030", "https://evil.example"] {
+            let headers = headers_for(origin);
+            assert!(headers.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(!headers.contains("Access-Control-Allow-Origin"), "{}", origin);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_stops_server_and_saves() {
+        let state = test_state();
+        let mut session = MinerSession::new("session-1".to_string(), "saver".to_string());
+        mine_for_session(&mut state.blockchain.lock().unwrap(), &mut session).unwrap();
+        state.sessions.lock().unwrap().insert(session.id.clone(), session);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stopped, stopped_rx) = std::sync::mpsc::channel();
+        let server_state = state.clone();
+        thread::spawn(move || {
+            serve(listener, &server_state).unwrap();
+            stopped.send(()).unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"GET /api/blockchain HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        state.shutdown();
+        stopped_rx.recv_timeout(Duration::from_secs(5)).expect("server should stop after shutdown()");
+
+        // Nothing listens any more, and what was in memory is on disk
+        assert!(TcpStream::connect(addr).is_err());
+        assert_eq!(Blockchain::load_from_file(&state.chain_file).unwrap().get_chain_length(), 2);
+        assert_eq!(load_sessions(&state.sessions_file)["session-1"].blocks_mined, 1);
+        remove_files(&state);
+    }
+
+    #[test]
+    fn test_chain_export() {
+        let state = test_state();
+        for _ in 0..2 {
+            state.blockchain.lock().unwrap().mine_pending_transactions("miner".to_string()).unwrap();
+        }
+        let get = |path: &str| {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            route_request(&request, &state)
+        };
+        let indices = |contents: &str| {
+            let blocks: Vec<serde_json::Value> = serde_json::from_str(contents).unwrap();
+            blocks.iter().map(|block| block["index"].as_u64().unwrap()).collect::<Vec<_>>()
+        };
+
+        let (status_line, contents) = get("/api/chain");
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(indices(&contents), vec![0, 1, 2]);
+        let blocks: Vec<serde_json::Value> = serde_json::from_str(&contents).unwrap();
+        assert!(blocks[1]["rps_mining_result"]["total_games"].is_u64());
+
+        // The whole export parses back into the chain's blocks
+        let exported: Vec<Block> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(exported[2].hash, state.blockchain.lock().unwrap().get_latest_block().hash);
+
+        assert_eq!(indices(&get("/api/chain?from=1&to=2").1), vec![1]);
+        assert_eq!(indices(&get("/api/chain?from=1").1), vec![1, 2]);
+        assert_eq!(indices(&get("/api/chain?to=1").1), vec![0]);
+        assert!(indices(&get("/api/chain?from=5").1).is_empty());
+
+        let (status_line, contents) = get("/api/chain?from=one");
+        assert_eq!(status_line, "HTTP/1.1 400 BAD REQUEST");
+        assert_eq!(contents, "Invalid from: expected a number");
+        assert_eq!(get("/api/chain?to=-1").0, "HTTP/1.1 400 BAD REQUEST");
+    }
 }
@@ -0,0 +1,177 @@
+// Just enough of RFC 6455 for the TCP server to push updates: the opening
+// handshake, unmasked text frames going out, and noticing when the client
+// closes. Anything else the client sends is ignored.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+// Appended to the client's key before hashing, per RFC 6455 section 1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const FINAL_FRAME: u8 = 0x80;
+
+// How long to wait for an update before checking whether the client has gone
+const UPDATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long to wait on the client's side of the socket each time round
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The `Sec-WebSocket-Accept` value answering a client's `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()))
+}
+
+pub fn handshake_response(key: &str) -> String {
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+/// Writes `text` as a single unmasked text frame, as servers send them
+pub fn write_text_frame<W: Write>(writer: &mut W, text: &str) -> std::io::Result<()> {
+    let payload = text.as_bytes();
+    let mut header = vec![FINAL_FRAME | OPCODE_TEXT];
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Completes the handshake, then forwards each update to the client until
+/// the client closes, the connection drops, or the updates stop. The
+/// receiver is dropped on return, which ends the subscription.
+pub fn serve_updates(mut stream: TcpStream, key: &str, updates: Receiver<String>) -> std::io::Result<()> {
+    stream.write_all(handshake_response(key).as_bytes())?;
+    stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL))?;
+
+    let mut buffer = [0; 1024];
+    loop {
+        match updates.recv_timeout(UPDATE_POLL_INTERVAL) {
+            Ok(json) => write_text_frame(&mut stream, &json)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        match stream.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(_) if buffer[0] & 0x0F == OPCODE_CLOSE => {
+                // Echo the close back, as the closing handshake asks
+                let _ = stream.write_all(&[FINAL_FRAME | OPCODE_CLOSE, 0]);
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut schedule = [0u32; 80];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            schedule[i] = (schedule[i - 3] ^ schedule[i - 8] ^ schedule[i - 14] ^ schedule[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, &word) in schedule.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // The worked example from RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        assert_eq!(hex::encode(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex::encode(sha1(&[b'a'; 64])), "0098ba824b5c16427bd7a1122a5a442a25ec644d");
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+    }
+
+    #[test]
+    fn test_text_frame_lengths() {
+        let mut frame = Vec::new();
+        write_text_frame(&mut frame, "hi").unwrap();
+        assert_eq!(frame, [0x81, 2, b'h', b'i']);
+
+        let text = "x".repeat(300);
+        let mut frame = Vec::new();
+        write_text_frame(&mut frame, &text).unwrap();
+        assert_eq!(frame[..4], [0x81, 126, 1, 44]);
+        assert_eq!(frame.len(), 4 + 300);
+    }
+}